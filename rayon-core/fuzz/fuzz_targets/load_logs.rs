@@ -0,0 +1,78 @@
+//! Feed arbitrary bytes to the logs loader, which must never panic nor loop.
+//! Whatever loads must also be safe to analyze with any public analysis.
+//! Run with `cargo fuzz run load_logs` from the `rayon-core` directory.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rayon_core::{EventKinds, RawEvent, RawLogs, SubgraphMetric};
+use std::io::sink;
+
+fn analyze(logs: &RawLogs) {
+    let _ = logs.validate();
+    let _ = logs.subgraph_report();
+    for metric in [
+        SubgraphMetric::InclusiveTime,
+        SubgraphMetric::ExclusiveTime,
+        SubgraphMetric::Count,
+        SubgraphMetric::Work,
+        SubgraphMetric::InclusiveWork,
+        SubgraphMetric::Throughput,
+    ]
+    .iter()
+    {
+        let _ = logs.subgraph_report_sorted_by(*metric);
+    }
+    let _ = logs.statistics().parallel_efficiency();
+    let _ = logs.grain_report(1_000).small_fraction();
+    let _ = logs.estimated_sequential_time();
+    let _ = logs.ci_line();
+    logs.resolved_events().for_each(|events| events.for_each(drop));
+    for (id, label) in logs.labels.iter().enumerate() {
+        let _ = logs.label_id(label);
+        let _ = logs.label_color(id);
+        let _ = logs.label_timeline(label);
+    }
+    let _ = logs.labels_ids();
+    let _ = logs.tasks_threads();
+    let _ = logs.unterminated_tasks();
+    let tree = logs.task_tree();
+    tree.depth_first().for_each(drop);
+    let _ = logs.fork_depth_histogram();
+    let _ = logs.queue_depth_timeline(16);
+    let _ = logs.occupancy_by_label(16);
+    logs.sweep((0..16).map(|i| i << 60), |_, state| {
+        let _ = state.busy_threads();
+    });
+    let _ = logs.idle_gaps();
+    let _ = logs.markers();
+    let _ = logs.counters();
+    let _ = logs.phases();
+    if let [start, .., end] = &logs.labels[..] {
+        let _ = logs.markers_bounds(start, end);
+    }
+    let _ = logs.blocked_times();
+    let _ = logs.task_metrics();
+    let _ = logs.parallel_depths();
+    let _ = logs.task_priorities();
+    let _ = logs.merged_tasks();
+    let _ = logs.task_subgraph();
+    let _ = logs.downsample(4);
+    let _ = logs.filter_kinds(EventKinds::TASK_START | EventKinds::TASK_END);
+    let mut coalesced = logs.filter_kinds(EventKinds::ALL);
+    coalesced.coalesce_subgraphs(1_000);
+    let _ = coalesced.validate();
+    for events in &logs.thread_events {
+        RawEvent::task_durations(events).for_each(drop);
+    }
+    let _ = logs.encoded_size();
+    let _ = logs.write_to(&mut sink());
+    let _ = logs.write_compact_to(&mut sink());
+    let _ = logs.to_spans_json(&mut sink());
+    let _ = logs.write_chrome_trace(&mut sink());
+    let _ = logs.dump(&mut sink());
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(logs) = RawLogs::read_from(&mut &data[..]) {
+        analyze(&logs);
+    }
+});
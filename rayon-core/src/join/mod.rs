@@ -3,8 +3,12 @@ use crate::latch::SpinLatch;
 use crate::registry::{self, WorkerThread};
 use crate::unwind;
 use std::any::Any;
+#[cfg(feature = "join_labels")]
+use std::panic::Location;
 
-use crate::tasks_logs::{log, next_task_id, now};
+#[cfg(feature = "join_labels")]
+use crate::tasks_logs::join_labels;
+use crate::tasks_logs::{log, log_parallel_depth, log_task_metric, next_task_id, now, TaskContext};
 use crate::FnContext;
 use crate::RawEvent;
 
@@ -92,6 +96,7 @@ mod test;
 /// closure, that panic will be propagated and hence `join()` will
 /// panic with the same panic value. If both closures panic, `join()`
 /// will panic with the panic value from the first closure.
+#[cfg_attr(feature = "join_labels", track_caller)]
 pub fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
 where
     A: FnOnce() -> RA + Send,
@@ -114,6 +119,7 @@ where
 /// the second job is stolen by a different thread, or if
 /// `join_context` was called from outside the thread pool to begin
 /// with.
+#[cfg_attr(feature = "join_labels", track_caller)]
 pub fn join_context<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
 where
     A: FnOnce(FnContext) -> RA + Send,
@@ -131,9 +137,16 @@ where
         move |migrated| f(FnContext::new(migrated))
     }
 
+    #[cfg(feature = "join_labels")]
+    let labels = join_labels(Location::caller());
+    #[cfg(not(feature = "join_labels"))]
+    let labels: Option<(&'static str, &'static str)> = None;
+    // task b might get stolen: it takes our context with it
+    let context = TaskContext::current();
     let id_c = next_task_id();
     let id_a = next_task_id();
     let id_b = next_task_id();
+    log_task_metric();
     logs!(
         RawEvent::Child(id_a),
         RawEvent::Child(id_b),
@@ -141,15 +154,32 @@ where
     );
 
     let results = registry::in_worker(|worker_thread, injected| unsafe {
+        // if we come from outside the pool, task a runs on another thread: it takes our
+        // context with it too
+        let _context = if injected {
+            Some(context.enter())
+        } else {
+            None
+        };
         // Create virtual wrapper for task b; this all has to be
         // done here so that the stack frame can keep it all live
         // long enough.
         let job_b = StackJob::new(
             call_b(|arg| {
-                log(RawEvent::TaskStart(id_b, now()));
-                let result = oper_b(arg);
-                logs!(RawEvent::Child(id_c), RawEvent::TaskEnd(now()));
-                result
+                context.run(|| {
+                    log(RawEvent::TaskStart(id_b, now()));
+                    log_parallel_depth();
+                    if let Some((_, label_b)) = labels {
+                        subgraph_logs!(RawEvent::SubgraphStart(label_b));
+                    }
+                    let result = oper_b(arg);
+                    if let Some((_, label_b)) = labels {
+                        subgraph_logs!(RawEvent::SubgraphEnd(label_b, 1));
+                    }
+                    log_task_metric();
+                    logs!(RawEvent::Child(id_c), RawEvent::TaskEnd(now()));
+                    result
+                })
             }),
             SpinLatch::new(worker_thread),
         );
@@ -158,11 +188,19 @@ where
 
         // Execute task a; hopefully b gets stolen in the meantime.
         log(RawEvent::TaskStart(id_a, now()));
+        log_parallel_depth();
+        if let Some((label_a, _)) = labels {
+            subgraph_logs!(RawEvent::SubgraphStart(label_a));
+        }
         let status_a = unwind::halt_unwinding(call_a(oper_a, injected));
         let result_a = match status_a {
             Ok(v) => v,
             Err(err) => join_recover_from_panic(worker_thread, &job_b.latch, err),
         };
+        if let Some((label_a, _)) = labels {
+            subgraph_logs!(RawEvent::SubgraphEnd(label_a, 1));
+        }
+        log_task_metric();
         logs!(RawEvent::Child(id_c), RawEvent::TaskEnd(now()));
 
         // Now that task A has finished, try to pop job B from the
@@ -184,7 +222,9 @@ where
             } else {
                 // Local deque is empty. Time to steal from other
                 // threads.
+                log(RawEvent::BlockStart(now()));
                 worker_thread.wait_until(&job_b.latch);
+                log(RawEvent::BlockEnd(now()));
                 debug_assert!(job_b.latch.probe());
                 break;
             }
@@ -193,6 +233,7 @@ where
         (result_a, job_b.into_result())
     });
     log(RawEvent::TaskStart(id_c, now()));
+    log_parallel_depth();
     results
 }
 
@@ -59,9 +59,14 @@ pub use self::registry::ThreadBuilder;
 pub use self::scope::{scope, Scope};
 pub use self::scope::{scope_fifo, ScopeFifo};
 pub use self::spawn::{spawn, spawn_fifo};
-use self::tasks_logs::Storage;
+pub use self::tasks_logs::wire_format;
+use self::tasks_logs::SharedLogs;
 pub use self::tasks_logs::{
-    custom_subgraph, subgraph, Logger, RawEvent, RawLogs, SubGraphId, TaskId, TimeStamp,
+    custom_subgraph, custom_subgraph_f64, custom_subgraph_with_unit, log_external_task,
+    log_task_priority, sampled_subgraph, subgraph, tag_value, ActiveState, EventKinds, GrainReport,
+    LogError, LogStatistics, Logger, ManualClock, ParallelRegion, PhaseStatistics, PoolConfig,
+    Provenance, RawEvent, RawLogs, RawLogsBuilder, ResolvedEvent, SubGraphId, SubgraphMetric,
+    SubgraphStats, TaskId, TaskTree, ThreadStatistics, TimeStamp, EXTERNAL_TASK_FLAG,
 };
 pub use self::thread_pool::current_thread_has_pending_tasks;
 pub use self::thread_pool::current_thread_index;
@@ -152,13 +157,22 @@ pub struct ThreadPoolBuilder<S = DefaultSpawn> {
     breadth_first: bool,
 
     /// If we save tasks logs or not and where.
-    tasks_logger: Option<
-        std::sync::Arc<
-            std::sync::Mutex<
-                std::collections::LinkedList<std::sync::Arc<Storage<RawEvent<&'static str>>>>,
-            >,
-        >,
-    >,
+    tasks_logger: Option<std::sync::Arc<SharedLogs>>,
+
+    /// If false, only subgraphs get logged and not each task.
+    log_tasks: bool,
+
+    /// If true, each branch of each `join` gets logged as a subgraph.
+    log_joins: bool,
+
+    /// If true, subgraphs nested in another subgraph of the same thread are not logged.
+    log_root_subgraphs_only: bool,
+
+    /// Closure called at the end of each logged task to measure a value for it.
+    task_metric: Option<std::sync::Arc<TaskMetric>>,
+
+    /// If true, the NUMA node of each worker thread is captured when it starts.
+    log_numa_nodes: bool,
 }
 
 /// Contains the rayon thread pool configuration. Use [`ThreadPoolBuilder`] instead.
@@ -183,6 +197,11 @@ type StartHandler = dyn Fn(usize) + Send + Sync;
 /// Note that this same closure may be invoked multiple times in parallel.
 type ExitHandler = dyn Fn(usize) + Send + Sync;
 
+/// The type for a closure that gets invoked at the end of each logged task
+/// and returns a value to record for the task.
+/// Note that this same closure may be invoked multiple times in parallel.
+type TaskMetric = dyn Fn() -> u64 + Send + Sync;
+
 // NB: We can't `#[derive(Default)]` because `S` is left ambiguous.
 impl Default for ThreadPoolBuilder {
     fn default() -> Self {
@@ -196,6 +215,11 @@ impl Default for ThreadPoolBuilder {
             spawn_handler: DefaultSpawn,
             breadth_first: false,
             tasks_logger: None,
+            log_tasks: true,
+            log_joins: false,
+            log_root_subgraphs_only: false,
+            task_metric: None,
+            log_numa_nodes: false,
         }
     }
 }
@@ -380,6 +404,11 @@ impl<S> ThreadPoolBuilder<S> {
             exit_handler: self.exit_handler,
             breadth_first: self.breadth_first,
             tasks_logger: self.tasks_logger,
+            log_tasks: self.log_tasks,
+            log_joins: self.log_joins,
+            log_root_subgraphs_only: self.log_root_subgraphs_only,
+            task_metric: self.task_metric,
+            log_numa_nodes: self.log_numa_nodes,
         }
     }
 
@@ -537,6 +566,100 @@ impl<S> ThreadPoolBuilder<S> {
         self.breadth_first
     }
 
+    /// Choose if automatic tasks events (`TaskStart`, `TaskEnd` and `Child`
+    /// from `join`, `scope`,...) get logged by the pool's threads.
+    ///
+    /// Tasks events vastly outnumber subgraph events. Setting this to `false`
+    /// only records the events emitted by `subgraph` and `custom_subgraph`
+    /// (each subgraph in its own task, to time it) which is enough for coarse
+    /// analysis and reduces logs size by orders of magnitude. Default is `true`.
+    ///
+    /// This only has an effect if the pool is logged (see `Logger::pool_builder`).
+    pub fn log_tasks(mut self, log_tasks: bool) -> Self {
+        self.log_tasks = log_tasks;
+        self
+    }
+
+    fn get_log_tasks(&self) -> bool {
+        self.log_tasks
+    }
+
+    /// Choose if both branches of each `join` get automatically logged as subgraphs,
+    /// labeled by the `join` call site.
+    ///
+    /// This displays the fork-join structure of divide and conquer algorithms without any
+    /// manual `subgraph` call. It is off by default because of its overhead.
+    ///
+    /// This only has an effect if the pool is logged (see `Logger::pool_builder`) and
+    /// with the `join_labels` feature, which makes `join` and `join_context` take their
+    /// call site as a hidden argument.
+    /// Without this feature, this is a no-op: joins are logged as usual but never as
+    /// subgraphs.
+    pub fn log_joins(mut self, log_joins: bool) -> Self {
+        self.log_joins = log_joins;
+        self
+    }
+
+    fn get_log_joins(&self) -> bool {
+        self.log_joins
+    }
+
+    /// Choose if only outermost subgraphs get logged.
+    ///
+    /// When on, a subgraph started inside another subgraph is not logged (its op still
+    /// runs of course), even from tasks stolen by other threads or spawned inside the
+    /// outer subgraph. This gives a clean high level timeline of
+    /// the main phases of a computation with very small logs.
+    /// `join` branches logged with `log_joins` are not affected.
+    ///
+    /// This only has an effect if the pool is logged (see `Logger::pool_builder`).
+    pub fn log_root_subgraphs_only(mut self, log_root_subgraphs_only: bool) -> Self {
+        self.log_root_subgraphs_only = log_root_subgraphs_only;
+        self
+    }
+
+    fn get_log_root_subgraphs_only(&self) -> bool {
+        self.log_root_subgraphs_only
+    }
+
+    /// Sets a callback to measure a value (for example a hardware counter or a number of
+    /// processed bytes) at the end of each logged task.
+    ///
+    /// The value is recorded as a `RawEvent::TaskMetric` just before the task's end.
+    /// The closure is only invoked if set and if tasks are logged.
+    /// Note that this same closure may be invoked multiple times in parallel.
+    ///
+    /// This only has an effect if the pool is logged (see `Logger::pool_builder`).
+    pub fn task_metric<M>(mut self, task_metric: M) -> Self
+    where
+        M: Fn() -> u64 + Send + Sync + 'static,
+    {
+        self.task_metric = Some(std::sync::Arc::new(task_metric));
+        self
+    }
+
+    fn get_task_metric(&self) -> Option<std::sync::Arc<TaskMetric>> {
+        self.task_metric.clone()
+    }
+
+    /// Choose if the NUMA node of each worker thread is captured when it starts
+    /// (see `RawLogs::numa_node`).
+    ///
+    /// This allows grouping threads by node to reveal cross-node stealing.
+    /// Nodes are only known on linux: we record the node of the cpu the thread runs on
+    /// when starting, which is its affinity if threads are pinned (for example by a
+    /// start handler). Elsewhere, or if not available, nodes are unknown.
+    ///
+    /// This only has an effect if the pool is logged (see `Logger::pool_builder`).
+    pub fn log_numa_nodes(mut self, log_numa_nodes: bool) -> Self {
+        self.log_numa_nodes = log_numa_nodes;
+        self
+    }
+
+    fn get_log_numa_nodes(&self) -> bool {
+        self.log_numa_nodes
+    }
+
     /// Takes the current thread start callback, leaving `None`.
     fn take_start_handler(&mut self) -> Option<Box<StartHandler>> {
         self.start_handler.take()
@@ -704,6 +827,11 @@ impl<S> fmt::Debug for ThreadPoolBuilder<S> {
             spawn_handler: _,
             ref breadth_first,
             ref tasks_logger,
+            ref log_tasks,
+            ref log_joins,
+            ref log_root_subgraphs_only,
+            ref task_metric,
+            ref log_numa_nodes,
         } = *self;
 
         // Just print `Some(<closure>)` or `None` to the debug
@@ -718,6 +846,7 @@ impl<S> fmt::Debug for ThreadPoolBuilder<S> {
         let panic_handler = panic_handler.as_ref().map(|_| ClosurePlaceholder);
         let start_handler = start_handler.as_ref().map(|_| ClosurePlaceholder);
         let exit_handler = exit_handler.as_ref().map(|_| ClosurePlaceholder);
+        let task_metric = task_metric.as_ref().map(|_| ClosurePlaceholder);
 
         struct LoggerHolder(bool);
         impl fmt::Debug for LoggerHolder {
@@ -739,6 +868,11 @@ impl<S> fmt::Debug for ThreadPoolBuilder<S> {
             .field("exit_handler", &exit_handler)
             .field("breadth_first", &breadth_first)
             .field("tasks_logger", &LoggerHolder(tasks_logger.is_some()))
+            .field("log_tasks", &log_tasks)
+            .field("log_joins", &log_joins)
+            .field("log_root_subgraphs_only", &log_root_subgraphs_only)
+            .field("task_metric", &task_metric)
+            .field("log_numa_nodes", &log_numa_nodes)
             .finish()
     }
 }
@@ -3,11 +3,12 @@ use crate::latch::{AsCoreLatch, CoreLatch, CountLatch, Latch, LockLatch, SpinLat
 use crate::log::Event::*;
 use crate::log::Logger;
 use crate::sleep::Sleep;
-use crate::tasks_logs::{RawEvent, Storage};
+use crate::tasks_logs::{PoolConfig, SharedLogs};
 use crate::unwind;
 use crate::util::leak;
 use crate::{
-    ErrorKind, ExitHandler, PanicHandler, StartHandler, ThreadPoolBuildError, ThreadPoolBuilder,
+    ErrorKind, ExitHandler, PanicHandler, StartHandler, TaskMetric, ThreadPoolBuildError,
+    ThreadPoolBuilder,
 };
 use crossbeam_deque::{Injector, Steal, Stealer, Worker};
 use std::any::Any;
@@ -157,13 +158,22 @@ pub(super) struct Registry {
     terminate_count: AtomicUsize,
 
     /// if tasks are logged and where
-    tasks_logger: Option<
-        std::sync::Arc<
-            std::sync::Mutex<
-                std::collections::LinkedList<std::sync::Arc<Storage<RawEvent<&'static str>>>>,
-            >,
-        >,
-    >,
+    tasks_logger: Option<Arc<SharedLogs>>,
+
+    /// if tasks events are logged or only subgraphs
+    log_tasks: bool,
+
+    /// if join branches are logged as subgraphs
+    log_joins: bool,
+
+    /// if only outermost subgraphs are logged
+    log_root_subgraphs_only: bool,
+
+    /// measure recorded at the end of each task
+    task_metric: Option<Arc<TaskMetric>>,
+
+    /// if the NUMA node of each thread is captured
+    log_numa_nodes: bool,
 }
 
 /// ////////////////////////////////////////////////////////////////////////
@@ -244,6 +254,14 @@ impl Registry {
             })
             .unzip();
 
+        if let Some(tasks_logger) = &builder.tasks_logger {
+            tasks_logger.record_pool_config(PoolConfig {
+                num_threads: n_threads,
+                stack_size: builder.get_stack_size(),
+                breadth_first,
+            });
+        }
+
         let logger = Logger::new(n_threads);
         let registry = Arc::new(Registry {
             logger: logger.clone(),
@@ -255,6 +273,11 @@ impl Registry {
             start_handler: builder.take_start_handler(),
             exit_handler: builder.take_exit_handler(),
             tasks_logger: builder.tasks_logger.clone(),
+            log_tasks: builder.get_log_tasks(),
+            log_joins: builder.get_log_joins(),
+            log_root_subgraphs_only: builder.get_log_root_subgraphs_only(),
+            task_metric: builder.get_task_metric(),
+            log_numa_nodes: builder.get_log_numa_nodes(),
         });
 
         // If we return early or panic, make sure to terminate existing threads.
@@ -825,8 +848,12 @@ unsafe fn main_loop(worker: Worker<JobRef>, registry: Arc<Registry>, index: usiz
     registry.thread_infos[index].primed.set();
     // tell him where we record logs
     if let Some(tasks_logger) = &registry.tasks_logger {
-        crate::tasks_logs::THREAD_LOGS
-            .with(|logs| tasks_logger.lock().unwrap().push_back(logs.clone()));
+        tasks_logger.register_current_thread(registry.log_numa_nodes);
+        crate::tasks_logs::LOG_TASKS.with(|l| l.set(registry.log_tasks));
+        crate::tasks_logs::LOG_JOINS.with(|l| l.set(registry.log_joins));
+        crate::tasks_logs::LOG_ROOT_SUBGRAPHS_ONLY
+            .with(|l| l.set(registry.log_root_subgraphs_only));
+        crate::tasks_logs::TASK_METRIC.with(|m| *m.borrow_mut() = registry.task_metric.clone());
     }
 
     // Worker threads should not panic. If they do, just abort, as the
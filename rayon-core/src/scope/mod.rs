@@ -7,6 +7,7 @@
 use crate::job::{HeapJob, JobFifo};
 use crate::latch::CountLatch;
 use crate::registry::{in_worker, Registry, WorkerThread};
+use crate::tasks_logs::TaskContext;
 use crate::unwind;
 use std::any::Any;
 use std::fmt;
@@ -449,9 +450,10 @@ impl<'scope> Scope<'scope> {
         BODY: FnOnce(&Scope<'scope>) + Send + 'scope,
     {
         self.base.increment();
+        let context = TaskContext::current();
         unsafe {
             let job_ref = Box::new(HeapJob::new(move || {
-                self.base.execute_job(move || body(self))
+                self.base.execute_job(move || context.run(|| body(self)))
             }))
             .as_job_ref();
 
@@ -490,9 +492,10 @@ impl<'scope> ScopeFifo<'scope> {
         BODY: FnOnce(&ScopeFifo<'scope>) + Send + 'scope,
     {
         self.base.increment();
+        let context = TaskContext::current();
         unsafe {
             let job_ref = Box::new(HeapJob::new(move || {
-                self.base.execute_job(move || body(self))
+                self.base.execute_job(move || context.run(|| body(self)))
             }))
             .as_job_ref();
 
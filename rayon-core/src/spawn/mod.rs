@@ -1,5 +1,6 @@
 use crate::job::*;
 use crate::registry::Registry;
+use crate::tasks_logs::TaskContext;
 use crate::unwind;
 use std::mem;
 use std::sync::Arc;
@@ -93,8 +94,9 @@ where
 
     Box::new(HeapJob::new({
         let registry = registry.clone();
+        let context = TaskContext::current();
         move || {
-            match unwind::halt_unwinding(func) {
+            match unwind::halt_unwinding(|| context.run(func)) {
                 Ok(()) => {}
                 Err(err) => {
                     registry.handle_panic(err);
@@ -0,0 +1,1996 @@
+//! Post-processing of recorded logs.
+
+use super::{without_tasks_logs, RawEvent, RawLogs, ResolvedEvent, SubGraphId, TaskId, TimeStamp};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// An rgb color.
+type Color = (u8, u8, u8);
+
+/// One execution of a subgraph.
+#[derive(Debug)]
+pub(super) struct SubgraphSpan {
+    pub(super) thread: usize,
+    pub(super) label: SubGraphId,
+    pub(super) start: TimeStamp,
+    pub(super) end: TimeStamp,
+    pub(super) work: Option<f64>,
+}
+
+/// How many tasks are too small, see `RawLogs::grain_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrainReport {
+    /// Tasks strictly shorter than this duration (in nanoseconds) are considered small.
+    pub threshold: TimeStamp,
+    /// Number of terminated tasks on each thread.
+    pub tasks: Vec<usize>,
+    /// Number of small tasks on each thread.
+    pub small_tasks: Vec<usize>,
+}
+
+impl GrainReport {
+    /// Fraction of all tasks which are small (0 if there are no tasks).
+    /// A high fraction means the grain size should be increased.
+    pub fn small_fraction(&self) -> f64 {
+        let tasks: usize = self.tasks.iter().sum();
+        if tasks == 0 {
+            0.0
+        } else {
+            self.small_tasks.iter().sum::<usize>() as f64 / tasks as f64
+        }
+    }
+}
+
+/// Activity of one thread, see `RawLogs::statistics`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThreadStatistics {
+    /// Time spent inside tasks (tasks nested in other tasks are not counted twice).
+    pub busy_time: TimeStamp,
+    /// Time spent between the end of a task and the start of the next one.
+    pub idle_time: TimeStamp,
+    /// Number of terminated tasks.
+    pub tasks: usize,
+    /// Sum of the works declared by the subgraphs of each label.
+    pub works: HashMap<SubGraphId, f64>,
+}
+
+/// Activity during one phase of the program, see `RawLogs::statistics`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PhaseStatistics {
+    /// Label of the phase (see `Logger::begin_phase`).
+    pub label: SubGraphId,
+    /// Duration of the phase.
+    pub duration: TimeStamp,
+    /// Time all threads spent inside tasks during the phase.
+    pub busy_time: TimeStamp,
+}
+
+/// Summary of logs, see `RawLogs::statistics`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogStatistics {
+    /// Statistics of each thread, including threads without any event.
+    pub threads: Vec<ThreadStatistics>,
+    /// Statistics of each phase, in order (see `RawLogs::phases`).
+    pub phases: Vec<PhaseStatistics>,
+    /// Time between the first and the last timed events of all threads.
+    pub span: TimeStamp,
+}
+
+impl LogStatistics {
+    /// Fraction of the span threads spent inside tasks (0 for logs without any time).
+    pub fn parallel_efficiency(&self) -> f64 {
+        if self.span == 0 || self.threads.is_empty() {
+            0.0
+        } else {
+            let busy = self.threads.iter().fold(0, |busy: TimeStamp, thread| {
+                busy.saturating_add(thread.busy_time)
+            });
+            busy as f64 / (self.span as f64 * self.threads.len() as f64)
+        }
+    }
+}
+
+/// Write given string as a json string.
+pub(super) fn write_json_string<W: Write>(string: &str, out: &mut W) -> io::Result<()> {
+    write!(out, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    write!(out, "\"")
+}
+
+/// Statistics of all executions of a subgraph label, see `RawLogs::subgraph_report`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubgraphStats {
+    /// Id of the label.
+    pub label: SubGraphId,
+    /// Number of executions.
+    pub count: usize,
+    /// Sum of the works declared by all executions.
+    pub work: f64,
+    /// Sum of the works of all executions including the works of the subgraphs nested
+    /// in them ("total work under this region").
+    /// Like times, nesting is taken from the subgraphs stack of each thread: nested
+    /// subgraphs run by tasks stolen by other threads are not included while subgraphs of
+    /// unrelated tasks a thread runs while waiting inside this one are.
+    pub inclusive_work: f64,
+    /// Sum of the durations of all executions, including nested subgraphs
+    /// (the "total" time of a profiler).
+    pub inclusive_time: TimeStamp,
+    /// Time during which this label was the innermost active subgraph
+    /// (the "self" time of a profiler).
+    pub exclusive_time: TimeStamp,
+    /// Largest number of threads simultaneously inside this label.
+    /// A label which never exceeds 1 is executed serially.
+    pub peak_concurrency: usize,
+}
+
+impl SubgraphStats {
+    /// Work per nanosecond of inclusive time (0 if the label took no time).
+    pub fn throughput(&self) -> f64 {
+        if self.inclusive_time == 0 {
+            0.0
+        } else {
+            self.work / self.inclusive_time as f64
+        }
+    }
+}
+
+/// Metric ordering a subgraph report, see `RawLogs::subgraph_report_sorted_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubgraphMetric {
+    /// `SubgraphStats::inclusive_time`.
+    InclusiveTime,
+    /// `SubgraphStats::exclusive_time`.
+    ExclusiveTime,
+    /// `SubgraphStats::count`.
+    Count,
+    /// `SubgraphStats::work`.
+    Work,
+    /// `SubgraphStats::inclusive_work`.
+    InclusiveWork,
+    /// `SubgraphStats::throughput`.
+    Throughput,
+}
+
+impl SubgraphMetric {
+    /// Value of this metric for given statistics.
+    pub fn of(self, stats: &SubgraphStats) -> f64 {
+        match self {
+            SubgraphMetric::InclusiveTime => stats.inclusive_time as f64,
+            SubgraphMetric::ExclusiveTime => stats.exclusive_time as f64,
+            SubgraphMetric::Count => stats.count as f64,
+            SubgraphMetric::Work => stats.work,
+            SubgraphMetric::InclusiveWork => stats.inclusive_work,
+            SubgraphMetric::Throughput => stats.throughput(),
+        }
+    }
+}
+
+/// A set of kinds of events, see `RawLogs::filter_kinds`.
+/// Sets combine with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct EventKinds(u16);
+
+impl EventKinds {
+    /// No event.
+    pub const NONE: EventKinds = EventKinds(0);
+    /// `TaskStart` events.
+    pub const TASK_START: EventKinds = EventKinds(1);
+    /// `TaskEnd` events.
+    pub const TASK_END: EventKinds = EventKinds(1 << 1);
+    /// `Child` events.
+    pub const CHILD: EventKinds = EventKinds(1 << 2);
+    /// `TaskMetric` events.
+    pub const TASK_METRIC: EventKinds = EventKinds(1 << 3);
+    /// `SubgraphStart` events.
+    pub const SUBGRAPH_START: EventKinds = EventKinds(1 << 4);
+    /// `SubgraphEnd` events.
+    pub const SUBGRAPH_END: EventKinds = EventKinds(1 << 5);
+    /// `SubgraphEndF64` events.
+    pub const SUBGRAPH_END_F64: EventKinds = EventKinds(1 << 6);
+    /// `BlockStart` events.
+    pub const BLOCK_START: EventKinds = EventKinds(1 << 7);
+    /// `BlockEnd` events.
+    pub const BLOCK_END: EventKinds = EventKinds(1 << 8);
+    /// `ParallelDepth` events.
+    pub const PARALLEL_DEPTH: EventKinds = EventKinds(1 << 9);
+    /// `Marker` events.
+    pub const MARKER: EventKinds = EventKinds(1 << 10);
+    /// `MergedTasks` events.
+    pub const MERGED_TASKS: EventKinds = EventKinds(1 << 11);
+    /// `TaskPriority` events.
+    pub const TASK_PRIORITY: EventKinds = EventKinds(1 << 12);
+    /// `PhaseStart` events.
+    pub const PHASE_START: EventKinds = EventKinds(1 << 13);
+    /// `Counter` events.
+    pub const COUNTER: EventKinds = EventKinds(1 << 14);
+    /// Starts and ends of tasks.
+    pub const TASKS: EventKinds = EventKinds(Self::TASK_START.0 | Self::TASK_END.0);
+    /// Starts and ends of subgraphs.
+    pub const SUBGRAPHS: EventKinds =
+        EventKinds(Self::SUBGRAPH_START.0 | Self::SUBGRAPH_END.0 | Self::SUBGRAPH_END_F64.0);
+    /// Starts and ends of blocked times.
+    pub const BLOCKS: EventKinds = EventKinds(Self::BLOCK_START.0 | Self::BLOCK_END.0);
+    /// All events.
+    pub const ALL: EventKinds = EventKinds((1 << 15) - 1);
+
+    /// Kind of given event.
+    pub fn of<S>(event: &RawEvent<S>) -> EventKinds {
+        match event {
+            RawEvent::TaskStart(..) => EventKinds::TASK_START,
+            RawEvent::TaskEnd(_) => EventKinds::TASK_END,
+            RawEvent::Child(_) => EventKinds::CHILD,
+            RawEvent::TaskMetric(_) => EventKinds::TASK_METRIC,
+            RawEvent::SubgraphStart(_) => EventKinds::SUBGRAPH_START,
+            RawEvent::SubgraphEnd(..) => EventKinds::SUBGRAPH_END,
+            RawEvent::SubgraphEndF64(..) => EventKinds::SUBGRAPH_END_F64,
+            RawEvent::BlockStart(_) => EventKinds::BLOCK_START,
+            RawEvent::BlockEnd(_) => EventKinds::BLOCK_END,
+            RawEvent::ParallelDepth(_) => EventKinds::PARALLEL_DEPTH,
+            RawEvent::Marker(..) => EventKinds::MARKER,
+            RawEvent::MergedTasks(_) => EventKinds::MERGED_TASKS,
+            RawEvent::TaskPriority(_) => EventKinds::TASK_PRIORITY,
+            RawEvent::PhaseStart(..) => EventKinds::PHASE_START,
+            RawEvent::Counter(..) => EventKinds::COUNTER,
+        }
+    }
+
+    /// Do all kinds of `other` belong to this set ?
+    pub fn contains(self, other: EventKinds) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Does any kind of `other` belong to this set ?
+    pub fn intersects(self, other: EventKinds) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl std::ops::BitOr for EventKinds {
+    type Output = EventKinds;
+    fn bitor(self, other: EventKinds) -> EventKinds {
+        EventKinds(self.0 | other.0)
+    }
+}
+
+/// Tasks organized as a tree following `Child` events, see `RawLogs::task_tree`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskTree {
+    /// Tasks without any parent, ordered by start time.
+    pub roots: Vec<TaskId>,
+    /// For each task with several parents, the parents it was not attached to.
+    /// Continuations of `join` have both branches as parents so this is expected
+    /// for logs of rayon.
+    pub extra_parents: HashMap<TaskId, Vec<TaskId>>,
+    children: HashMap<TaskId, Vec<TaskId>>,
+    parents: HashMap<TaskId, TaskId>,
+}
+
+impl TaskTree {
+    /// Children of given task, ordered by start time (tasks never started come last).
+    pub fn children(&self, id: TaskId) -> &[TaskId] {
+        self.children.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+    /// Parent given task is attached to, `None` for roots.
+    pub fn parent(&self, id: TaskId) -> Option<TaskId> {
+        self.parents.get(&id).cloned()
+    }
+    /// Iterate on all tasks, depth first, with their depth (0 for roots).
+    pub fn depth_first(&self) -> impl Iterator<Item = (TaskId, usize)> + '_ {
+        let mut remaining: Vec<(TaskId, usize)> =
+            self.roots.iter().rev().map(|id| (*id, 0)).collect();
+        std::iter::from_fn(move || {
+            let (id, depth) = remaining.pop()?;
+            remaining.extend(
+                self.children(id)
+                    .iter()
+                    .rev()
+                    .map(|child| (*child, depth + 1)),
+            );
+            Some((id, depth))
+        })
+    }
+}
+
+/// Tasks and subgraphs active on each thread at a given time, see `RawLogs::sweep`.
+#[derive(Debug)]
+pub struct ActiveState {
+    threads: Vec<ThreadState>,
+}
+
+/// Active tasks and subgraphs of one thread.
+#[derive(Debug, Default)]
+struct ThreadState {
+    tasks: Vec<TaskId>,
+    subgraphs: Vec<SubGraphId>,
+    /// subgraphs whose end was seen but which only end at the next timed event
+    pending_ends: usize,
+}
+
+impl ThreadState {
+    fn apply(&mut self, event: &RawEvent<SubGraphId>) {
+        match event {
+            RawEvent::TaskStart(id, _) => {
+                self.end_subgraphs();
+                self.tasks.push(*id);
+            }
+            RawEvent::TaskEnd(_) => {
+                self.end_subgraphs();
+                self.tasks.pop();
+            }
+            RawEvent::SubgraphStart(label) => self.subgraphs.push(*label),
+            RawEvent::SubgraphEnd(..) | RawEvent::SubgraphEndF64(..) => self.pending_ends += 1,
+            RawEvent::Child(_)
+            | RawEvent::TaskMetric(_)
+            | RawEvent::BlockStart(_)
+            | RawEvent::BlockEnd(_)
+            | RawEvent::ParallelDepth(_)
+            | RawEvent::Marker(..)
+            | RawEvent::PhaseStart(..)
+            | RawEvent::Counter(..)
+            | RawEvent::MergedTasks(_)
+            | RawEvent::TaskPriority(_) => (),
+        }
+    }
+    fn end_subgraphs(&mut self) {
+        for _ in 0..self.pending_ends {
+            self.subgraphs.pop();
+        }
+        self.pending_ends = 0;
+    }
+}
+
+impl ActiveState {
+    /// Number of threads in the logs.
+    pub fn threads_number(&self) -> usize {
+        self.threads.len()
+    }
+    /// Active tasks of given thread (the innermost one last).
+    pub fn active_tasks(&self, thread: usize) -> &[TaskId] {
+        &self.threads[thread].tasks
+    }
+    /// Active subgraphs of given thread (the innermost one last).
+    pub fn active_subgraphs(&self, thread: usize) -> &[SubGraphId] {
+        &self.threads[thread].subgraphs
+    }
+    /// Number of threads executing a task.
+    pub fn busy_threads(&self) -> usize {
+        self.threads.iter().filter(|t| !t.tasks.is_empty()).count()
+    }
+}
+
+impl<S> RawEvent<S> {
+    /// Return the time of timed events, `None` for all other events.
+    pub fn time(&self) -> Option<TimeStamp> {
+        match self {
+            RawEvent::TaskStart(_, time)
+            | RawEvent::TaskEnd(time)
+            | RawEvent::BlockStart(time)
+            | RawEvent::BlockEnd(time)
+            | RawEvent::Marker(_, time)
+            | RawEvent::PhaseStart(_, time)
+            | RawEvent::Counter(_, _, time) => Some(*time),
+            RawEvent::Child(_)
+            | RawEvent::TaskMetric(_)
+            | RawEvent::SubgraphStart(_)
+            | RawEvent::SubgraphEnd(..)
+            | RawEvent::SubgraphEndF64(..)
+            | RawEvent::ParallelDepth(_)
+            | RawEvent::MergedTasks(_)
+            | RawEvent::TaskPriority(_) => None,
+        }
+    }
+
+    /// Return the work amount of subgraphs end events (integer or not), `None` for all
+    /// other events.
+    pub fn subgraph_work(&self) -> Option<f64> {
+        match self {
+            RawEvent::SubgraphEnd(_, work) => Some(*work as f64),
+            RawEvent::SubgraphEndF64(_, bits) => Some(f64::from_bits(*bits)),
+            _ => None,
+        }
+    }
+
+    /// Iterate on `(task id, duration)` for all tasks of given thread events, in the order
+    /// in which they end.
+    /// Starts and ends are matched with a stack so nested tasks are handled correctly.
+    /// Tasks which never end are skipped.
+    pub fn task_durations(
+        events: &[RawEvent<S>],
+    ) -> impl Iterator<Item = (TaskId, TimeStamp)> + '_ {
+        durations_of(events.iter())
+    }
+}
+
+/// Iterate on `(task id, duration)` for all terminated tasks of given thread events.
+pub(super) fn durations_of<'a, S: 'a, I>(
+    events: I,
+) -> impl Iterator<Item = (TaskId, TimeStamp)> + 'a
+where
+    I: Iterator<Item = &'a RawEvent<S>> + 'a,
+{
+    spans_of(events).map(|(id, start, end)| (id, end.saturating_sub(start)))
+}
+
+/// Iterate on `(task id, start, end)` for all terminated tasks of given thread events,
+/// in the order in which they end.
+pub(super) fn spans_of<'a, S: 'a, I>(
+    events: I,
+) -> impl Iterator<Item = (TaskId, TimeStamp, TimeStamp)> + 'a
+where
+    I: Iterator<Item = &'a RawEvent<S>> + 'a,
+{
+    let mut active_tasks = Vec::new();
+    events.filter_map(move |event| match event {
+        RawEvent::TaskStart(id, start) => {
+            active_tasks.push((*id, *start));
+            None
+        }
+        RawEvent::TaskEnd(end) => active_tasks.pop().map(|(id, start)| (id, start, *end)),
+        _ => None,
+    })
+}
+
+/// Check balance of tasks and subgraphs of one thread.
+/// Validate given threads (the first one having index `first`) in parallel,
+/// returning problems in threads order.
+fn validate_threads(threads: &[Vec<RawEvent<SubGraphId>>], first: usize) -> Vec<String> {
+    if threads.len() <= 1 {
+        return threads
+            .iter()
+            .flat_map(|events| validate_thread(events, first))
+            .collect();
+    }
+    let (left, right) = threads.split_at(threads.len() / 2);
+    let (mut problems, right_problems) = crate::join(
+        || validate_threads(left, first),
+        || validate_threads(right, first + left.len()),
+    );
+    problems.extend(right_problems);
+    problems
+}
+
+fn validate_thread(events: &[RawEvent<SubGraphId>], thread: usize) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut active_tasks = Vec::new();
+    let mut subgraphs = Vec::new();
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            RawEvent::TaskStart(id, _) => active_tasks.push(*id),
+            RawEvent::TaskEnd(_) => {
+                if active_tasks.pop().is_none() {
+                    errors.push(format!(
+                        "thread {}: task end without task at event {}",
+                        thread, index
+                    ))
+                }
+            }
+            RawEvent::SubgraphStart(label) => subgraphs.push(*label),
+            RawEvent::SubgraphEnd(label, _) | RawEvent::SubgraphEndF64(label, _) => {
+                match subgraphs.pop() {
+                    None => errors.push(format!(
+                        "thread {}: subgraph end without subgraph at event {}",
+                        thread, index
+                    )),
+                    Some(started) if started != *label => errors.push(format!(
+                        "thread {}: subgraph {} ends subgraph {} at event {}",
+                        thread, label, started, index
+                    )),
+                    Some(_) => (),
+                }
+            }
+            RawEvent::Child(_)
+            | RawEvent::TaskMetric(_)
+            | RawEvent::BlockStart(_)
+            | RawEvent::BlockEnd(_)
+            | RawEvent::ParallelDepth(_)
+            | RawEvent::Marker(..)
+            | RawEvent::PhaseStart(..)
+            | RawEvent::Counter(..)
+            | RawEvent::MergedTasks(_)
+            | RawEvent::TaskPriority(_) => (),
+        }
+    }
+    errors.extend(
+        active_tasks
+            .into_iter()
+            .map(|id| format!("thread {}: task {} never ends", thread, id)),
+    );
+    errors.extend(
+        subgraphs
+            .into_iter()
+            .map(|label| format!("thread {}: subgraph {} never ends", thread, label)),
+    );
+    errors
+}
+
+/// Duration of the longest chain of dependent tasks (the span) among the terminated
+/// tasks of given threads events.
+/// Dependencies towards tasks which are not (yet) terminated are ignored.
+pub(super) fn span_of<'a, S: 'a, T>(threads: impl Iterator<Item = T>) -> TimeStamp
+where
+    T: Iterator<Item = &'a RawEvent<S>>,
+{
+    // duration and children of each terminated task
+    let mut tasks: HashMap<TaskId, (TimeStamp, Vec<TaskId>)> = HashMap::new();
+    for events in threads {
+        let mut active_tasks: Vec<(TaskId, TimeStamp, Vec<TaskId>)> = Vec::new();
+        for event in events {
+            match event {
+                RawEvent::TaskStart(id, start) => active_tasks.push((*id, *start, Vec::new())),
+                RawEvent::Child(child) => {
+                    if let Some((_, _, children)) = active_tasks.last_mut() {
+                        children.push(*child)
+                    }
+                }
+                RawEvent::TaskEnd(end) => {
+                    if let Some((id, start, children)) = active_tasks.pop() {
+                        tasks.insert(id, (end.saturating_sub(start), children));
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    // longest paths, in topological order
+    let mut parents_count: HashMap<TaskId, usize> = HashMap::new();
+    for child in tasks.values().flat_map(|(_, children)| children) {
+        if tasks.contains_key(child) {
+            *parents_count.entry(*child).or_insert(0) += 1;
+        }
+    }
+    let mut ready: Vec<TaskId> = tasks
+        .keys()
+        .filter(|id| !parents_count.contains_key(id))
+        .cloned()
+        .collect();
+    let mut longest_paths_to: HashMap<TaskId, TimeStamp> = HashMap::new();
+    let mut span = 0;
+    while let Some(id) = ready.pop() {
+        let (duration, children) = &tasks[&id];
+        let path = longest_paths_to
+            .get(&id)
+            .cloned()
+            .unwrap_or(0)
+            .saturating_add(*duration);
+        span = span.max(path);
+        for child in children.iter().filter(|child| tasks.contains_key(child)) {
+            let longest = longest_paths_to.entry(*child).or_insert(0);
+            *longest = (*longest).max(path);
+            let remaining_parents = parents_count.get_mut(child).unwrap();
+            *remaining_parents -= 1;
+            if *remaining_parents == 0 {
+                ready.push(*child);
+            }
+        }
+    }
+    span
+}
+
+/// If `events` start with a task running no other task, with balanced subgraphs and
+/// shorter than `threshold`, return its number of events, the label of its first subgraph
+/// and the number of original tasks it stands for.
+fn short_leaf_task(
+    events: &[RawEvent<SubGraphId>],
+    threshold: TimeStamp,
+) -> Option<(usize, Option<SubGraphId>, usize)> {
+    let start = match events.first()? {
+        RawEvent::TaskStart(_, start) => *start,
+        _ => return None,
+    };
+    let mut label = None;
+    let mut depth = 0usize;
+    let mut count = 1;
+    for (index, event) in events.iter().enumerate().skip(1) {
+        match event {
+            RawEvent::TaskStart(..) => return None,
+            RawEvent::TaskEnd(end) => {
+                return if depth == 0 && end.saturating_sub(start) < threshold {
+                    Some((index + 1, label, count))
+                } else {
+                    None
+                };
+            }
+            RawEvent::SubgraphStart(started) => {
+                label = label.or(Some(*started));
+                depth += 1;
+            }
+            RawEvent::SubgraphEnd(..) | RawEvent::SubgraphEndF64(..) => {
+                depth = depth.checked_sub(1)?
+            }
+            RawEvent::MergedTasks(merged) => count = *merged,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Merge adjacent tasks of one thread which are shorter than `threshold` and run the same
+/// subgraph.
+/// Merged tasks keep the events of the first of them and span until the end of the last.
+fn downsample_thread(
+    events: &[RawEvent<SubGraphId>],
+    threshold: TimeStamp,
+) -> Vec<RawEvent<SubGraphId>> {
+    let mut downsampled = Vec::new();
+    // index of the count of the task being merged, its count and its label
+    let mut merging: Option<(usize, usize, Option<SubGraphId>)> = None;
+    let mut index = 0;
+    while index < events.len() {
+        let task = short_leaf_task(&events[index..], threshold);
+        match (task, &mut merging) {
+            (Some((length, label, count)), Some((count_index, merged, merging_label)))
+                if *merging_label == label =>
+            {
+                *merged += count;
+                downsampled[*count_index] = RawEvent::MergedTasks(*merged);
+                *downsampled.last_mut().unwrap() = events[index + length - 1].clone();
+                index += length;
+            }
+            (Some((length, label, count)), _) => {
+                downsampled.push(events[index].clone());
+                merging = Some((downsampled.len(), count, label));
+                downsampled.push(RawEvent::MergedTasks(count));
+                downsampled.extend(
+                    events[index + 1..index + length]
+                        .iter()
+                        .filter(|event| !matches!(event, RawEvent::MergedTasks(_)))
+                        .cloned(),
+                );
+                index += length;
+            }
+            (None, _) => {
+                merging = None;
+                downsampled.push(events[index].clone());
+                index += 1;
+            }
+        }
+    }
+    downsampled.retain(|event| *event != RawEvent::MergedTasks(1));
+    downsampled
+}
+
+/// Return the end of a subgraph standing for two executions ending with given events
+/// (of the same label), adding their works.
+fn merge_subgraph_ends(
+    first: &RawEvent<SubGraphId>,
+    second: &RawEvent<SubGraphId>,
+) -> RawEvent<SubGraphId> {
+    match (first, second) {
+        (RawEvent::SubgraphEnd(_, first_work), RawEvent::SubgraphEnd(label, second_work)) => {
+            RawEvent::SubgraphEnd(*label, first_work + second_work)
+        }
+        (_, RawEvent::SubgraphEnd(label, _)) | (_, RawEvent::SubgraphEndF64(label, _)) => {
+            let work = first.subgraph_work().unwrap_or(0.0) + second.subgraph_work().unwrap_or(0.0);
+            RawEvent::SubgraphEndF64(*label, work.to_bits())
+        }
+        _ => second.clone(),
+    }
+}
+
+/// Coalesce subgraphs of one thread, see `RawLogs::coalesce_subgraphs`.
+fn coalesce_thread(
+    events: &[RawEvent<SubGraphId>],
+    max_gap: TimeStamp,
+) -> Vec<RawEvent<SubGraphId>> {
+    // merged subgraph ends are left as holes, removed at the end
+    let mut coalesced: Vec<Option<RawEvent<SubGraphId>>> = Vec::with_capacity(events.len());
+    // for each open subgraph, the end of the execution it continues (if merged)
+    let mut merged_ends: Vec<Option<RawEvent<SubGraphId>>> = Vec::new();
+    // index of each subgraph start or end in `coalesced` (ignoring merged ones) with the
+    // first and last times of the events between it and the previous one
+    let mut boundaries: Vec<(usize, Option<TimeStamp>, Option<TimeStamp>)> = Vec::new();
+    // first and last times of the events since the last boundary
+    let mut times: (Option<TimeStamp>, Option<TimeStamp>) = (None, None);
+    // subgraphs are timed by the tasks events around them (see `subgraph_spans`):
+    // time of the last task event seen and of the next one after each event
+    let task_time = |event: &RawEvent<SubGraphId>| match event {
+        RawEvent::TaskStart(_, time) | RawEvent::TaskEnd(time) => Some(*time),
+        _ => None,
+    };
+    let mut last_task_time = None;
+    let mut next_task_times: Vec<Option<TimeStamp>> = events
+        .iter()
+        .rev()
+        .scan(None, |next_time, event| {
+            let time = *next_time;
+            *next_time = task_time(event).or(*next_time);
+            Some(time)
+        })
+        .collect();
+    next_task_times.reverse();
+    for (event, next_task_time) in events.iter().zip(next_task_times) {
+        match event {
+            RawEvent::SubgraphStart(label) => {
+                // without any time between the executions the gap is unknown
+                let gap = match times {
+                    (Some(first), Some(last)) => Some(last.saturating_sub(first)),
+                    _ => None,
+                };
+                let previous_end = boundaries.last().filter(|(index, _, _)| {
+                    matches!(
+                        coalesced[*index],
+                        Some(RawEvent::SubgraphEnd(ended, _))
+                            | Some(RawEvent::SubgraphEndF64(ended, _)) if ended == *label
+                    )
+                });
+                match previous_end {
+                    Some(&(index, first, last)) if matches!(gap, Some(gap) if gap <= max_gap) => {
+                        // continue the previous execution, the times before its end
+                        // are now after the last boundary
+                        boundaries.pop();
+                        times = (first.or(times.0), times.1.or(last));
+                        merged_ends.push(coalesced[index].take());
+                    }
+                    _ => {
+                        merged_ends.push(None);
+                        boundaries.push((coalesced.len(), times.0, times.1));
+                        times = (None, None);
+                        coalesced.push(Some(event.clone()));
+                    }
+                }
+            }
+            RawEvent::SubgraphEnd(label, _) | RawEvent::SubgraphEndF64(label, _) => {
+                let merged_end = merged_ends.pop().flatten();
+                let end = match (coalesced.last(), merged_end) {
+                    // nothing happened in this execution, which did not last
+                    (Some(Some(RawEvent::SubgraphStart(started))), None)
+                        if started == label
+                            && last_task_time.is_some()
+                            && last_task_time == next_task_time =>
+                    {
+                        coalesced.pop();
+                        if let Some((_, first, last)) = boundaries.pop() {
+                            times = (first, last);
+                        }
+                        continue;
+                    }
+                    (_, Some(merged_end)) => merge_subgraph_ends(&merged_end, event),
+                    (_, None) => event.clone(),
+                };
+                boundaries.push((coalesced.len(), times.0, times.1));
+                times = (None, None);
+                coalesced.push(Some(end));
+            }
+            _ => {
+                if let Some(time) = event.time() {
+                    times = (times.0.or(Some(time)), Some(time));
+                }
+                last_task_time = task_time(event).or(last_task_time);
+                coalesced.push(Some(event.clone()));
+            }
+        }
+    }
+    coalesced.into_iter().flatten().collect()
+}
+
+impl RawLogs {
+    /// Colors used by default for labels.
+    pub const DEFAULT_PALETTE: [Color; 12] = [
+        (141, 211, 199),
+        (255, 255, 179),
+        (190, 186, 218),
+        (251, 128, 114),
+        (128, 177, 211),
+        (253, 180, 98),
+        (179, 222, 105),
+        (252, 205, 229),
+        (217, 217, 217),
+        (188, 128, 189),
+        (204, 235, 197),
+        (255, 237, 111),
+    ];
+
+    /// Count tasks shorter than `threshold` nanoseconds on each thread.
+    /// Too many small tasks indicate the scheduling cost is not amortized.
+    pub fn grain_report(&self, threshold: TimeStamp) -> GrainReport {
+        let (tasks, small_tasks) = self
+            .thread_events
+            .iter()
+            .map(|events| {
+                RawEvent::task_durations(events).fold((0, 0), |(tasks, small), (_, duration)| {
+                    (tasks + 1, small + (duration < threshold) as usize)
+                })
+            })
+            .unzip();
+        GrainReport {
+            threshold,
+            tasks,
+            small_tasks,
+        }
+    }
+
+    /// Estimate the sequential execution time (T1) as the sum of the durations of all
+    /// terminated tasks.
+    /// Tasks nested in another task of the same thread are not counted twice.
+    ///
+    /// Dividing it by the parallel execution time gives a speedup without a second,
+    /// sequential, run. This is only an estimate: it includes the logging overhead and
+    /// ignores cache effects, which differ between sequential and parallel executions.
+    pub fn estimated_sequential_time(&self) -> TimeStamp {
+        self.thread_events
+            .iter()
+            .map(|events| {
+                let mut active_tasks = Vec::new();
+                let mut total: TimeStamp = 0;
+                for event in events {
+                    match event {
+                        RawEvent::TaskStart(_, start) => active_tasks.push(*start),
+                        RawEvent::TaskEnd(end) => {
+                            if let Some(start) = active_tasks.pop() {
+                                // only outermost tasks count
+                                if active_tasks.is_empty() {
+                                    total = total.saturating_add(end.saturating_sub(start));
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                total
+            })
+            .fold(0, TimeStamp::saturating_add)
+    }
+
+    /// Return a one line summary for grepping in continuous integration logs, like
+    /// `RAYON_SUMMARY threads=16 span_ms=812 work_ms=11240 speedup=13.8 eff=0.86`.
+    ///
+    /// `span_ms` is the elapsed time between the first and last events, `work_ms` the
+    /// `estimated_sequential_time`, `speedup` their ratio and `eff` the speedup per thread.
+    /// Keys and their order are stable.
+    pub fn ci_line(&self) -> String {
+        let threads = self.thread_events.len();
+        let elapsed = self
+            .time_bounds()
+            .map(|(start, end)| end - start)
+            .unwrap_or(0);
+        let work = self.estimated_sequential_time();
+        let speedup = if elapsed == 0 {
+            0.0
+        } else {
+            work as f64 / elapsed as f64
+        };
+        let efficiency = if threads == 0 {
+            0.0
+        } else {
+            speedup / threads as f64
+        };
+        format!(
+            "RAYON_SUMMARY threads={} span_ms={} work_ms={} speedup={:.1} eff={:.2}",
+            threads,
+            elapsed / 1_000_000,
+            work / 1_000_000,
+            speedup,
+            efficiency
+        )
+    }
+
+    /// Iterate on each thread's events with labels instead of labels ids.
+    pub fn resolved_events(
+        &self,
+    ) -> impl Iterator<Item = impl Iterator<Item = ResolvedEvent<'_>> + '_> + '_ {
+        self.thread_events.iter().map(move |events| {
+            events.iter().map(move |event| match event {
+                RawEvent::TaskStart(id, time) => RawEvent::TaskStart(*id, *time),
+                RawEvent::TaskEnd(time) => RawEvent::TaskEnd(*time),
+                RawEvent::Child(id) => RawEvent::Child(*id),
+                RawEvent::TaskMetric(value) => RawEvent::TaskMetric(*value),
+                RawEvent::BlockStart(time) => RawEvent::BlockStart(*time),
+                RawEvent::BlockEnd(time) => RawEvent::BlockEnd(*time),
+                RawEvent::ParallelDepth(depth) => RawEvent::ParallelDepth(*depth),
+                RawEvent::MergedTasks(count) => RawEvent::MergedTasks(*count),
+                RawEvent::TaskPriority(priority) => RawEvent::TaskPriority(*priority),
+                RawEvent::Marker(label, time) => RawEvent::Marker(&self.labels[*label][..], *time),
+                RawEvent::PhaseStart(label, time) => {
+                    RawEvent::PhaseStart(&self.labels[*label][..], *time)
+                }
+                RawEvent::SubgraphStart(label) => RawEvent::SubgraphStart(&self.labels[*label][..]),
+                RawEvent::SubgraphEnd(label, size) => {
+                    RawEvent::SubgraphEnd(&self.labels[*label][..], *size)
+                }
+                RawEvent::SubgraphEndF64(label, bits) => {
+                    RawEvent::SubgraphEndF64(&self.labels[*label][..], *bits)
+                }
+                RawEvent::Counter(label, value, time) => {
+                    RawEvent::Counter(&self.labels[*label][..], *value, *time)
+                }
+            })
+        })
+    }
+
+    /// Return the label of given id.
+    pub fn label(&self, id: SubGraphId) -> Option<&str> {
+        self.labels.get(id).map(|label| label.as_str())
+    }
+
+    /// Return the id of given label.
+    /// This scans all labels: use `labels_ids` instead for looking up many labels.
+    pub fn label_id(&self, name: &str) -> Option<SubGraphId> {
+        self.labels.iter().position(|label| label == name)
+    }
+
+    /// Return the id of each label (the first one if a label appears several times).
+    pub fn labels_ids(&self) -> HashMap<&str, SubGraphId> {
+        let mut ids = HashMap::with_capacity(self.labels.len());
+        for (id, label) in self.labels.iter().enumerate() {
+            ids.entry(label.as_str()).or_insert(id);
+        }
+        ids
+    }
+
+    /// Return the index of the thread which executed the task of given id
+    /// (`None` for unknown ids).
+    /// This scans all events: use `tasks_threads` instead for looking up many tasks.
+    pub fn task_thread(&self, id: TaskId) -> Option<usize> {
+        self.thread_events.iter().position(|events| {
+            events
+                .iter()
+                .any(|event| matches!(event, RawEvent::TaskStart(task, _) if *task == id))
+        })
+    }
+
+    /// Return the index of the thread which executed each task.
+    pub fn tasks_threads(&self) -> HashMap<TaskId, usize> {
+        self.thread_events
+            .iter()
+            .enumerate()
+            .flat_map(|(thread, events)| {
+                events.iter().filter_map(move |event| match event {
+                    RawEvent::TaskStart(id, _) => Some((*id, thread)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    /// Return the display color of given label.
+    /// The color only depends on the label's string (hashed with a stable hash)
+    /// so all exports agree and colors are consistent between different runs.
+    ///
+    /// Panics if `id` is not a valid label.
+    pub fn label_color(&self, id: SubGraphId) -> Color {
+        self.label_color_in(id, &Self::DEFAULT_PALETTE)
+    }
+
+    /// Like `label_color` but choose among given (non empty) palette.
+    pub fn label_color_in(&self, id: SubGraphId, palette: &[Color]) -> Color {
+        // fnv-1a hash
+        let hash = self.labels[id]
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        palette[(hash % palette.len() as u64) as usize]
+    }
+
+    /// Return all tasks which started but never ended, as `(thread index, task id)`.
+    /// A panic or a missing instrumentation can lead to such tasks which will then be displayed
+    /// as running forever.
+    pub fn unterminated_tasks(&self) -> Vec<(usize, TaskId)> {
+        self.thread_events
+            .iter()
+            .enumerate()
+            .flat_map(|(thread, events)| {
+                let mut active_tasks = Vec::new();
+                for event in events {
+                    match event {
+                        RawEvent::TaskStart(id, _) => active_tasks.push(*id),
+                        RawEvent::TaskEnd(_) => {
+                            active_tasks.pop();
+                        }
+                        _ => (),
+                    }
+                }
+                active_tasks.into_iter().map(move |id| (thread, id))
+            })
+            .collect()
+    }
+
+    /// Return first and last timestamps of the logs (None if nothing is timed).
+    fn time_bounds(&self) -> Option<(TimeStamp, TimeStamp)> {
+        let mut times = self
+            .thread_events
+            .iter()
+            .flatten()
+            .filter_map(RawEvent::time);
+        let first = times.next()?;
+        Some(times.fold((first, first), |(start, end), time| {
+            (start.min(time), end.max(time))
+        }))
+    }
+
+    /// Return `samples` evenly spaced times covering the whole logs.
+    fn sample_times(&self, samples: usize) -> Vec<TimeStamp> {
+        let (start, end) = match self.time_bounds() {
+            Some(bounds) if samples > 0 => bounds,
+            _ => return Vec::new(),
+        };
+        let steps = (samples - 1).max(1) as TimeStamp;
+        (0..samples as TimeStamp)
+            // in 128 bits since the product does not fit in a timestamp for long logs
+            .map(|i| start + ((end - start) as u128 * i as u128 / steps as u128) as TimeStamp)
+            .collect()
+    }
+
+    /// Return for each task the time at which it became ready to run.
+    /// `Child` events are not timed but they are logged just before their parent task ends,
+    /// so we use the time of the next timed event on the same thread.
+    /// A task with several parents is ready when the last one registers it.
+    fn ready_times(&self) -> HashMap<TaskId, TimeStamp> {
+        fn mark_ready(ready_times: &mut HashMap<TaskId, TimeStamp>, id: TaskId, time: TimeStamp) {
+            let ready_time = ready_times.entry(id).or_insert(time);
+            *ready_time = (*ready_time).max(time);
+        }
+        let mut ready_times = HashMap::new();
+        for events in &self.thread_events {
+            let mut pending_children = Vec::new();
+            let mut last_time = 0;
+            for event in events {
+                last_time = match event {
+                    RawEvent::Child(id) => {
+                        pending_children.push(*id);
+                        continue;
+                    }
+                    RawEvent::TaskStart(_, time) | RawEvent::TaskEnd(time) => *time,
+                    _ => continue,
+                };
+                for child in pending_children.drain(..) {
+                    mark_ready(&mut ready_times, child, last_time);
+                }
+            }
+            for child in pending_children {
+                mark_ready(&mut ready_times, child, last_time);
+            }
+        }
+        ready_times
+    }
+
+    /// Rebuild the tree of tasks from `Child` events.
+    /// A task with several parents is attached to the one starting first and the others
+    /// are recorded in `TaskTree::extra_parents`.
+    pub fn task_tree(&self) -> TaskTree {
+        let mut starts: HashMap<TaskId, TimeStamp> = HashMap::new();
+        let mut all_parents: HashMap<TaskId, Vec<TaskId>> = HashMap::new();
+        for events in &self.thread_events {
+            let mut active_tasks = Vec::new();
+            for event in events {
+                match event {
+                    RawEvent::TaskStart(id, time) => {
+                        starts.insert(*id, *time);
+                        active_tasks.push(*id);
+                    }
+                    RawEvent::TaskEnd(_) => {
+                        active_tasks.pop();
+                    }
+                    RawEvent::Child(child) => {
+                        if let Some(parent) = active_tasks.last() {
+                            all_parents.entry(*child).or_default().push(*parent);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let start_order = |id: &TaskId| (starts.get(id).cloned().unwrap_or(TimeStamp::MAX), *id);
+        let mut tree = TaskTree::default();
+        for (child, mut parents) in all_parents {
+            parents.sort_by_key(start_order);
+            parents.dedup();
+            let parent = parents.remove(0);
+            tree.parents.insert(child, parent);
+            tree.children.entry(parent).or_default().push(child);
+            if !parents.is_empty() {
+                tree.extra_parents.insert(child, parents);
+            }
+        }
+        for children in tree.children.values_mut() {
+            children.sort_by_key(start_order);
+        }
+        tree.roots = starts
+            .keys()
+            .filter(|id| !tree.parents.contains_key(id))
+            .cloned()
+            .collect();
+        tree.roots.sort_by_key(start_order);
+        tree
+    }
+
+    /// Return the number of tasks at each depth of the tree of tasks (see `task_tree`),
+    /// roots being at depth 0.
+    /// Divide and conquer algorithms produce one level per recursion level: a long
+    /// sparse tail reveals unbalanced recursion.
+    pub fn fork_depth_histogram(&self) -> Vec<usize> {
+        let mut histogram = Vec::new();
+        for (_, depth) in self.task_tree().depth_first() {
+            if histogram.len() <= depth {
+                histogram.resize(depth + 1, 0);
+            }
+            histogram[depth] += 1;
+        }
+        histogram
+    }
+
+    /// Return for `samples` evenly spaced times the number of tasks which were ready
+    /// (registered as a child by all their parents) but not yet started.
+    /// Spikes indicate the scheduler falling behind.
+    pub fn queue_depth_timeline(&self, samples: usize) -> Vec<(TimeStamp, usize)> {
+        let ready_times = self.ready_times();
+        // +1 when a task gets ready, -1 when it starts
+        let mut points: Vec<(TimeStamp, i64)> = self
+            .thread_events
+            .iter()
+            .flatten()
+            .filter_map(|event| match event {
+                RawEvent::TaskStart(id, start) => ready_times
+                    .get(id)
+                    .map(|ready| vec![((*ready).min(*start), 1), (*start, -1)]),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        points.sort();
+        let mut points = points.into_iter().peekable();
+        let mut depth = 0;
+        self.sample_times(samples)
+            .into_iter()
+            .map(|time| {
+                while let Some((_, change)) = points.next_if(|(point, _)| *point <= time) {
+                    depth += change;
+                }
+                (time, depth as usize)
+            })
+            .collect()
+    }
+
+    /// Return for `samples` evenly spaced times how many threads are busy inside each label
+    /// (as `(label, threads number)`, sorted by label and omitting labels with no thread).
+    /// A thread is counted for the innermost subgraph of its active task only, wherever the
+    /// subgraph started (see `task_subgraph`).
+    /// Threads without any tasks events (see `ThreadPoolBuilder::log_tasks`) are counted for
+    /// their innermost active subgraph.
+    /// This is the data needed for a stacked area chart of what the pool is doing over time.
+    pub fn occupancy_by_label(&self, samples: usize) -> Vec<(TimeStamp, Vec<(SubGraphId, usize)>)> {
+        let tasks_subgraphs = self.tasks_subgraphs();
+        let logged_tasks: Vec<bool> = self
+            .thread_events
+            .iter()
+            .map(|events| {
+                events
+                    .iter()
+                    .any(|event| matches!(event, RawEvent::TaskStart(..)))
+            })
+            .collect();
+        let mut occupancy = Vec::new();
+        self.sweep(self.sample_times(samples).into_iter(), |time, state| {
+            let mut counts = vec![0; self.labels.len()];
+            for (thread, logged_tasks) in logged_tasks.iter().enumerate() {
+                let label = if *logged_tasks {
+                    state
+                        .active_tasks(thread)
+                        .last()
+                        .and_then(|task| tasks_subgraphs.get(task))
+                        .and_then(|stack| stack.last())
+                } else {
+                    state.active_subgraphs(thread).last()
+                };
+                if let Some(label) = label {
+                    counts[*label] += 1;
+                }
+            }
+            let busy_labels = counts
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, count)| count > 0)
+                .collect();
+            occupancy.push((time, busy_labels));
+        });
+        occupancy
+    }
+
+    /// Replay all events up to each of the given (non decreasing) times and call `f` with
+    /// the time and the tasks and subgraphs active at this time on each thread.
+    ///
+    /// A task is active from its start (included) to its end (excluded).
+    /// Subgraphs events are not timed: a subgraph is active from the time of the event
+    /// preceding its start to the time of the event following its end.
+    /// This is the basis of timeline analyses (see `occupancy_by_label` for an example).
+    pub fn sweep<F>(&self, points: impl Iterator<Item = TimeStamp>, mut f: F)
+    where
+        F: FnMut(TimeStamp, &ActiveState),
+    {
+        let mut state = ActiveState {
+            threads: self
+                .thread_events
+                .iter()
+                .map(|_| ThreadState::default())
+                .collect(),
+        };
+        let mut next_events = vec![0; self.thread_events.len()];
+        for point in points {
+            for ((events, next_event), thread) in self
+                .thread_events
+                .iter()
+                .zip(next_events.iter_mut())
+                .zip(state.threads.iter_mut())
+            {
+                while let Some(event) = events.get(*next_event) {
+                    match event {
+                        RawEvent::TaskStart(_, time) | RawEvent::TaskEnd(time) if *time > point => {
+                            break
+                        }
+                        _ => thread.apply(event),
+                    }
+                    *next_event += 1;
+                }
+                if *next_event == events.len() {
+                    thread.end_subgraphs();
+                }
+            }
+            f(point, &state);
+        }
+    }
+
+    /// Return all complete subgraphs executions, in order of their ends on each thread.
+    /// Subgraphs events are not timed but they are logged just after a task start and
+    /// just before a task end so we use the times of these surrounding tasks.
+    pub(super) fn subgraph_spans(&self) -> Vec<SubgraphSpan> {
+        let mut spans = Vec::new();
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            let mut stack = Vec::new();
+            let mut pending_ends: Vec<SubgraphSpan> = Vec::new();
+            let mut last_time = 0;
+            for event in events {
+                match event {
+                    RawEvent::TaskStart(_, time) | RawEvent::TaskEnd(time) => {
+                        last_time = *time;
+                        spans.extend(pending_ends.drain(..).map(|mut span| {
+                            span.end = last_time;
+                            span
+                        }));
+                    }
+                    RawEvent::SubgraphStart(label) => stack.push((*label, last_time)),
+                    RawEvent::SubgraphEnd(..) | RawEvent::SubgraphEndF64(..) => {
+                        if let Some((label, start)) = stack.pop() {
+                            pending_ends.push(SubgraphSpan {
+                                thread,
+                                label,
+                                start,
+                                end: start,
+                                work: event.subgraph_work(),
+                            })
+                        }
+                    }
+                    RawEvent::Child(_)
+                    | RawEvent::TaskMetric(_)
+                    | RawEvent::BlockStart(_)
+                    | RawEvent::BlockEnd(_)
+                    | RawEvent::ParallelDepth(_)
+                    | RawEvent::Marker(..)
+                    | RawEvent::PhaseStart(..)
+                    | RawEvent::Counter(..)
+                    | RawEvent::MergedTasks(_)
+                    | RawEvent::TaskPriority(_) => (),
+                }
+            }
+            spans.extend(pending_ends.into_iter().map(|mut span| {
+                span.end = last_time;
+                span
+            }));
+        }
+        spans
+    }
+
+    /// Return all `(thread, start, end)` executions of subgraphs with given label,
+    /// sorted by starting times.
+    /// The vector is empty if the label does not exist.
+    pub fn label_timeline(&self, label: &str) -> Vec<(usize, TimeStamp, TimeStamp)> {
+        let id = match self.label_id(label) {
+            Some(id) => id,
+            None => return Vec::new(),
+        };
+        let mut timeline: Vec<_> = self
+            .subgraph_spans()
+            .into_iter()
+            .filter(|span| span.label == id)
+            .map(|span| (span.thread, span.start, span.end))
+            .collect();
+        timeline.sort_by_key(|&(thread, start, _)| (start, thread));
+        timeline
+    }
+
+    /// Check that tasks and subgraphs starts and ends are balanced on each thread,
+    /// returning a description of each problem found (empty if the logs are consistent).
+    ///
+    /// Threads are checked in parallel but problems are sorted by thread index so that
+    /// the output is deterministic.
+    /// The tasks of the validation itself are not logged.
+    pub fn validate(&self) -> Vec<String> {
+        without_tasks_logs(|| validate_threads(&self.thread_events, 0))
+    }
+
+    /// Return statistics for each label (indexed by label id), replaying the subgraphs
+    /// stack of each thread.
+    /// Elapsed time is charged as exclusive time to the innermost open subgraph only,
+    /// so that the exclusive times of nested subgraphs sum up to the inclusive time of
+    /// their parent.
+    /// Recursive executions of a label count several times in its inclusive time and work.
+    /// Since nesting is replayed per thread, work done on other threads (stolen tasks)
+    /// is missing from the inclusive work of the subgraph which spawned it.
+    pub fn subgraph_report(&self) -> Vec<SubgraphStats> {
+        let mut report: Vec<SubgraphStats> = (0..self.labels.len())
+            .map(|label| SubgraphStats {
+                label,
+                count: 0,
+                work: 0.0,
+                inclusive_work: 0.0,
+                inclusive_time: 0,
+                exclusive_time: 0,
+                peak_concurrency: 0,
+            })
+            .collect();
+        for events in &self.thread_events {
+            // open subgraphs with their starting times
+            let mut stack: Vec<(SubGraphId, TimeStamp)> = Vec::new();
+            // subgraphs whose end was seen but which only end at the next timed event
+            let mut pending_ends = 0;
+            let mut last_time = None;
+            // works of the subgraphs nested in each open subgraph
+            let mut nested_works: Vec<f64> = Vec::new();
+            for event in events {
+                match event {
+                    RawEvent::TaskStart(_, time) | RawEvent::TaskEnd(time) => {
+                        if let (Some(&(label, _)), Some(last_time)) = (stack.last(), last_time) {
+                            report[label].exclusive_time = report[label]
+                                .exclusive_time
+                                .saturating_add(time.saturating_sub(last_time));
+                        }
+                        for _ in 0..pending_ends {
+                            if let Some((label, start)) = stack.pop() {
+                                report[label].inclusive_time = report[label]
+                                    .inclusive_time
+                                    .saturating_add(time.saturating_sub(start));
+                            }
+                        }
+                        pending_ends = 0;
+                        last_time = Some(*time);
+                    }
+                    RawEvent::SubgraphStart(label) => {
+                        stack.push((*label, last_time.unwrap_or(0)));
+                        nested_works.push(0.0);
+                    }
+                    RawEvent::SubgraphEnd(label, _) | RawEvent::SubgraphEndF64(label, _) => {
+                        let work = event.subgraph_work().unwrap_or(0.0);
+                        let inclusive_work = work + nested_works.pop().unwrap_or(0.0);
+                        if let Some(parent_work) = nested_works.last_mut() {
+                            *parent_work += inclusive_work;
+                        }
+                        let stats = &mut report[*label];
+                        stats.count += 1;
+                        stats.work += work;
+                        stats.inclusive_work += inclusive_work;
+                        pending_ends += 1;
+                    }
+                    RawEvent::Child(_)
+                    | RawEvent::TaskMetric(_)
+                    | RawEvent::BlockStart(_)
+                    | RawEvent::BlockEnd(_)
+                    | RawEvent::ParallelDepth(_)
+                    | RawEvent::Marker(..)
+                    | RawEvent::PhaseStart(..)
+                    | RawEvent::Counter(..)
+                    | RawEvent::MergedTasks(_)
+                    | RawEvent::TaskPriority(_) => (),
+                }
+            }
+            // ends after the last timed event end at this event
+            for (label, start) in stack.drain(stack.len().saturating_sub(pending_ends)..) {
+                report[label].inclusive_time = report[label]
+                    .inclusive_time
+                    .saturating_add(last_time.unwrap_or(0).saturating_sub(start));
+            }
+        }
+        // sweep over all subgraphs starts and ends (starts first on equal times)
+        let mut points: Vec<(TimeStamp, bool, SubGraphId, usize)> = self
+            .subgraph_spans()
+            .into_iter()
+            .flat_map(|span| {
+                vec![
+                    (span.start, false, span.label, span.thread),
+                    (span.end.max(span.start), true, span.label, span.thread),
+                ]
+            })
+            .collect();
+        points.sort_unstable();
+        // how many times each thread is inside each label (for recursive subgraphs)
+        let mut depths: HashMap<(SubGraphId, usize), usize> = HashMap::new();
+        let mut concurrency = vec![0; self.labels.len()];
+        for (_, is_end, label, thread) in points {
+            let depth = depths.entry((label, thread)).or_insert(0);
+            if is_end {
+                *depth -= 1;
+                if *depth == 0 {
+                    concurrency[label] -= 1;
+                }
+            } else {
+                *depth += 1;
+                if *depth == 1 {
+                    concurrency[label] += 1;
+                    let peak = &mut report[label].peak_concurrency;
+                    *peak = (*peak).max(concurrency[label]);
+                }
+            }
+        }
+        report
+    }
+
+    /// Return the subgraph report sorted by decreasing values of given metric.
+    /// The sort is stable so labels with equal values stay in order of their ids.
+    pub fn subgraph_report_sorted_by(&self, metric: SubgraphMetric) -> Vec<SubgraphStats> {
+        let mut report = self.subgraph_report();
+        report.sort_by(|a, b| {
+            metric
+                .of(b)
+                .partial_cmp(&metric.of(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        report
+    }
+
+    /// Return a numeric summary of the logs: busy time, idle time, tasks and works of
+    /// each thread, duration and busy time of each phase together with the span of the
+    /// whole logs.
+    pub fn statistics(&self) -> LogStatistics {
+        let phases_bounds = self.phases();
+        let mut phases: Vec<PhaseStatistics> = phases_bounds
+            .iter()
+            .map(|&(label, start, end)| PhaseStatistics {
+                label,
+                duration: end - start,
+                busy_time: 0,
+            })
+            .collect();
+        let threads = self
+            .thread_events
+            .iter()
+            .zip(self.idle_gaps())
+            .map(|(events, gaps)| {
+                let mut statistics = ThreadStatistics {
+                    idle_time: gaps
+                        .iter()
+                        .fold(0, |idle, (start, end)| idle.saturating_add(end - start)),
+                    ..Default::default()
+                };
+                let spans: Vec<_> = spans_of(events.iter()).collect();
+                statistics.tasks = spans.len();
+                // nested tasks end before their parents and start after them so, going
+                // backwards, a task is outermost if it starts before the last outermost one
+                let mut outer_start = None;
+                for &(_, start, end) in spans.iter().rev() {
+                    if !matches!(outer_start, Some(outer) if start >= outer) {
+                        statistics.busy_time = statistics
+                            .busy_time
+                            .saturating_add(end.saturating_sub(start));
+                        outer_start = Some(start);
+                        for (phase, &(_, phase_start, phase_end)) in
+                            phases.iter_mut().zip(&phases_bounds)
+                        {
+                            phase.busy_time = phase.busy_time.saturating_add(
+                                end.min(phase_end).saturating_sub(start.max(phase_start)),
+                            );
+                        }
+                    }
+                }
+                for event in events {
+                    if let RawEvent::SubgraphEnd(label, _) | RawEvent::SubgraphEndF64(label, _) =
+                        event
+                    {
+                        *statistics.works.entry(*label).or_insert(0.0) +=
+                            event.subgraph_work().unwrap_or(0.0);
+                    }
+                }
+                statistics
+            })
+            .collect();
+        LogStatistics {
+            threads,
+            phases,
+            span: self.time_bounds().map_or(0, |(start, end)| end - start),
+        }
+    }
+
+    /// Return for each thread all `(start, end)` intervals during which it was idle:
+    /// between the end of a task and the start of the next one, with no active task.
+    pub fn idle_gaps(&self) -> Vec<Vec<(TimeStamp, TimeStamp)>> {
+        self.thread_events
+            .iter()
+            .map(|events| {
+                let mut gaps = Vec::new();
+                let mut active_tasks = 0usize;
+                let mut idle_since = None;
+                for event in events {
+                    match event {
+                        RawEvent::TaskStart(_, start) => {
+                            if active_tasks == 0 {
+                                if let Some(end) = idle_since.take() {
+                                    if *start > end {
+                                        gaps.push((end, *start));
+                                    }
+                                }
+                            }
+                            active_tasks += 1;
+                        }
+                        RawEvent::TaskEnd(end) => {
+                            active_tasks = active_tasks.saturating_sub(1);
+                            if active_tasks == 0 {
+                                idle_since = Some(*end);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                gaps
+            })
+            .collect()
+    }
+
+    /// Return all `(thread, label, time)` markers (see `Logger::mark`), sorted by time.
+    pub fn markers(&self) -> Vec<(usize, SubGraphId, TimeStamp)> {
+        let mut markers: Vec<_> = self
+            .thread_events
+            .iter()
+            .enumerate()
+            .flat_map(|(thread, events)| {
+                events.iter().filter_map(move |event| match event {
+                    RawEvent::Marker(label, time) => Some((thread, *label, *time)),
+                    _ => None,
+                })
+            })
+            .collect();
+        markers.sort_by_key(|&(thread, _, time)| (time, thread));
+        markers
+    }
+
+    /// Return all `(thread, label, time, value)` counter values (see `tag_value`),
+    /// sorted by time.
+    pub fn counters(&self) -> Vec<(usize, SubGraphId, TimeStamp, u64)> {
+        let mut counters: Vec<_> = self
+            .thread_events
+            .iter()
+            .enumerate()
+            .flat_map(|(thread, events)| {
+                events.iter().filter_map(move |event| match event {
+                    RawEvent::Counter(label, value, time) => Some((thread, *label, *time, *value)),
+                    _ => None,
+                })
+            })
+            .collect();
+        counters.sort_by_key(|&(thread, _, time, _)| (time, thread));
+        counters
+    }
+
+    /// Return all `(label, start, end)` phases (see `Logger::begin_phase`), in order.
+    /// Each phase ends when the next one starts and the last one at the end of the logs.
+    pub fn phases(&self) -> Vec<(SubGraphId, TimeStamp, TimeStamp)> {
+        let mut starts: Vec<(TimeStamp, SubGraphId)> = self
+            .thread_events
+            .iter()
+            .flatten()
+            .filter_map(|event| match event {
+                RawEvent::PhaseStart(label, time) => Some((*time, *label)),
+                _ => None,
+            })
+            .collect();
+        starts.sort_by_key(|&(time, _)| time);
+        let logs_end = self.time_bounds().map(|(_, end)| end).unwrap_or(0);
+        starts
+            .iter()
+            .enumerate()
+            .map(|(index, &(start, label))| {
+                let end = starts
+                    .get(index + 1)
+                    .map(|&(next_start, _)| next_start)
+                    .unwrap_or_else(|| logs_end.max(start));
+                (label, start, end)
+            })
+            .collect()
+    }
+
+    /// Return the times of the first marker named `start` and of the first following
+    /// marker named `end`, delimiting a region of interest.
+    /// `None` if one of them is missing.
+    pub fn markers_bounds(&self, start: &str, end: &str) -> Option<(TimeStamp, TimeStamp)> {
+        let ids = self.labels_ids();
+        let (start, end) = (*ids.get(start)?, *ids.get(end)?);
+        let markers = self.markers();
+        let start_time = markers
+            .iter()
+            .find(|&&(_, label, _)| label == start)
+            .map(|&(_, _, time)| time)?;
+        markers
+            .iter()
+            .find(|&&(_, label, time)| label == end && time >= start_time)
+            .map(|&(_, _, end_time)| (start_time, end_time))
+    }
+
+    /// Return for each thread the time spent blocked in `join`, waiting for the other
+    /// branch without running any task.
+    /// Tasks stolen while waiting are not counted, so this is pure synchronization
+    /// overhead.
+    pub fn blocked_times(&self) -> Vec<TimeStamp> {
+        self.thread_events
+            .iter()
+            .map(|events| {
+                // for each open task or block: is it a block
+                let mut stack = Vec::new();
+                let mut last_time = 0;
+                let mut blocked: TimeStamp = 0;
+                for event in events {
+                    let time = match event {
+                        RawEvent::TaskStart(_, time)
+                        | RawEvent::TaskEnd(time)
+                        | RawEvent::BlockStart(time)
+                        | RawEvent::BlockEnd(time) => *time,
+                        _ => continue,
+                    };
+                    if stack.last() == Some(&true) {
+                        blocked = blocked.saturating_add(time.saturating_sub(last_time));
+                    }
+                    last_time = time;
+                    match event {
+                        RawEvent::TaskStart(..) => stack.push(false),
+                        RawEvent::BlockStart(_) => stack.push(true),
+                        _ => {
+                            stack.pop();
+                        }
+                    }
+                }
+                blocked
+            })
+            .collect()
+    }
+
+    /// Return the value recorded by the pool's `task_metric` callback for each task which
+    /// has one.
+    pub fn task_metrics(&self) -> HashMap<TaskId, u64> {
+        self.per_task(|event| match event {
+            RawEvent::TaskMetric(value) => Some(*value),
+            _ => None,
+        })
+    }
+
+    /// Return for each task the value `f` extracts from the events attached to it
+    /// (the ones between its start and its end, nested tasks excepted), for tasks with
+    /// at least one event for which `f` returns a value (the last one wins).
+    pub fn per_task<T>(
+        &self,
+        f: impl Fn(&RawEvent<SubGraphId>) -> Option<T>,
+    ) -> HashMap<TaskId, T> {
+        let mut values = HashMap::new();
+        for events in &self.thread_events {
+            let mut active_tasks = Vec::new();
+            for event in events {
+                match event {
+                    RawEvent::TaskStart(id, _) => active_tasks.push(*id),
+                    RawEvent::TaskEnd(_) => {
+                        active_tasks.pop();
+                    }
+                    _ => {
+                        if let (Some(id), Some(value)) = (active_tasks.last(), f(event)) {
+                            values.insert(*id, value);
+                        }
+                    }
+                }
+            }
+        }
+        values
+    }
+
+    /// Return the number of nested parallel iterators each task ran in, for tasks which
+    /// ran in at least one (see `RawEvent::ParallelDepth`).
+    /// Deep nesting reveals parallel iterators accidentally nested in one another,
+    /// over-subscribing the pool.
+    pub fn parallel_depths(&self) -> HashMap<TaskId, usize> {
+        self.per_task(|event| match event {
+            RawEvent::ParallelDepth(depth) => Some(*depth),
+            _ => None,
+        })
+    }
+
+    /// Return the priority of each task with a non zero priority (see `log_task_priority`).
+    pub fn task_priorities(&self) -> HashMap<TaskId, u8> {
+        self.per_task(|event| match event {
+            RawEvent::TaskPriority(priority) => Some(*priority),
+            _ => None,
+        })
+    }
+
+    /// Clean up noisy subgraphs events, on each thread:
+    /// - an execution of a label following an execution of the same label, with a gap of
+    ///   at most `max_gap` nanoseconds between them, is merged with it (adding works).
+    ///   The gap is measured with the timed events between the two executions: without any,
+    ///   it is unknown and executions are not merged;
+    /// - executions with no events inside are dropped if they provably did not last: when
+    ///   the tasks events just before and just after them have the same time.
+    ///
+    /// Tasks events are left untouched.
+    pub fn coalesce_subgraphs(&mut self, max_gap: TimeStamp) {
+        for events in &mut self.thread_events {
+            *events = coalesce_thread(events, max_gap);
+        }
+    }
+
+    /// Return the number of original tasks each task of downsampled logs stands for,
+    /// for tasks standing for more than one (see `RawLogs::downsample`).
+    pub fn merged_tasks(&self) -> HashMap<TaskId, usize> {
+        self.per_task(|event| match event {
+            RawEvent::MergedTasks(count) => Some(*count),
+            _ => None,
+        })
+    }
+
+    /// Return logs with at most `max_events_per_thread` events per thread when possible,
+    /// for displaying huge logs.
+    /// On each thread, adjacent short tasks running the same subgraph are merged into one
+    /// task standing for all of them (see `RawLogs::merged_tasks`), with a duration
+    /// threshold doubled until the thread fits.
+    /// Only the first merged task keeps its children and subgraphs, so the result is
+    /// meant for display and not for analyzing dependencies.
+    pub fn downsample(&self, max_events_per_thread: usize) -> RawLogs {
+        let thread_events = self
+            .thread_events
+            .iter()
+            .map(|events| {
+                let times = events.iter().filter_map(|event| match event {
+                    RawEvent::TaskStart(_, time) | RawEvent::TaskEnd(time) => Some(*time),
+                    _ => None,
+                });
+                let span = times.clone().max().unwrap_or(0) - times.min().unwrap_or(0);
+                let mut downsampled = events.clone();
+                let mut threshold: TimeStamp = 1;
+                // a saturated threshold cannot grow anymore
+                while downsampled.len() > max_events_per_thread
+                    && threshold <= span
+                    && threshold < TimeStamp::MAX
+                {
+                    threshold = threshold.saturating_mul(2);
+                    downsampled = downsample_thread(events, threshold);
+                }
+                downsampled
+            })
+            .collect();
+        RawLogs {
+            thread_events,
+            labels: self.labels.clone(),
+            units: self.units.clone(),
+            pool_config: self.pool_config.clone(),
+            numa_nodes: self.numa_nodes.clone(),
+            provenance: self.provenance.clone(),
+        }
+    }
+
+    /// Return logs keeping only events of given kinds, for example to lighten logs before
+    /// saving them.
+    /// Kinds going by pairs are kept together so that the result stays balanced: keeping
+    /// any of `TaskStart` or `TaskEnd` keeps both, and the same goes for subgraphs and
+    /// blocks.
+    /// Subgraphs are timed by the tasks around them and task metrics, parallel depths,
+    /// merged tasks and priorities describe the active task so keeping any of them also
+    /// keeps tasks.
+    pub fn filter_kinds(&self, kinds: EventKinds) -> RawLogs {
+        let mut kinds = kinds;
+        for pair in &[EventKinds::TASKS, EventKinds::SUBGRAPHS, EventKinds::BLOCKS] {
+            if kinds.intersects(*pair) {
+                kinds = kinds | *pair;
+            }
+        }
+        let within_tasks = EventKinds::SUBGRAPHS
+            | EventKinds::TASK_METRIC
+            | EventKinds::PARALLEL_DEPTH
+            | EventKinds::MERGED_TASKS
+            | EventKinds::TASK_PRIORITY;
+        if kinds.intersects(within_tasks) {
+            kinds = kinds | EventKinds::TASKS;
+        }
+        let thread_events = self
+            .thread_events
+            .iter()
+            .map(|events| {
+                events
+                    .iter()
+                    .filter(|event| kinds.contains(EventKinds::of(event)))
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        RawLogs {
+            thread_events,
+            labels: self.labels.clone(),
+            units: self.units.clone(),
+            pool_config: self.pool_config.clone(),
+            numa_nodes: self.numa_nodes.clone(),
+            provenance: self.provenance.clone(),
+        }
+    }
+
+    /// Return the subgraphs containing each task, the innermost one last.
+    ///
+    /// Subgraphs are inherited through `Child` events, whichever thread executes the child:
+    /// a task is inside the subgraphs its parent (see `task_tree`) was in when registering
+    /// it, and inside the subgraphs it starts itself.
+    /// Tasks without any parent are inside the subgraphs active on their thread when they
+    /// start.
+    fn tasks_subgraphs(&self) -> HashMap<TaskId, Vec<SubGraphId>> {
+        let tree = self.task_tree();
+        // thread and index of the start of each task
+        let mut starts: HashMap<TaskId, (usize, usize)> = HashMap::new();
+        // subgraphs each task starts in
+        let mut inherited: HashMap<TaskId, Vec<SubGraphId>> = HashMap::new();
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            let mut stack = Vec::new();
+            for (index, event) in events.iter().enumerate() {
+                match event {
+                    RawEvent::TaskStart(id, _) => {
+                        starts.insert(*id, (thread, index));
+                        if tree.parent(*id).is_none() {
+                            inherited.insert(*id, stack.clone());
+                        }
+                    }
+                    RawEvent::SubgraphStart(label) => stack.push(*label),
+                    RawEvent::SubgraphEnd(..) | RawEvent::SubgraphEndF64(..) => {
+                        stack.pop();
+                    }
+                    _ => (),
+                }
+            }
+        }
+        // parents come before their children
+        let mut subgraphs = HashMap::new();
+        for (id, _) in tree.depth_first() {
+            let (thread, start) = match starts.get(&id) {
+                Some(start) => *start,
+                None => continue,
+            };
+            let mut stack = inherited.remove(&id).unwrap_or_default();
+            let mut task_stack = stack.clone();
+            for event in &self.thread_events[thread][start + 1..] {
+                match event {
+                    RawEvent::TaskStart(..) | RawEvent::TaskEnd(_) => break,
+                    RawEvent::SubgraphStart(label) => {
+                        stack.push(*label);
+                        // the task starting the subgraph is inside it
+                        task_stack = stack.clone();
+                    }
+                    RawEvent::SubgraphEnd(..) | RawEvent::SubgraphEndF64(..) => {
+                        stack.pop();
+                    }
+                    RawEvent::Child(child) if tree.parent(*child) == Some(id) => {
+                        inherited.insert(*child, stack.clone());
+                    }
+                    _ => (),
+                }
+            }
+            subgraphs.insert(id, task_stack);
+        }
+        subgraphs
+    }
+
+    /// Return the innermost subgraph containing each task (`None` for tasks outside of
+    /// any subgraph).
+    /// Subgraphs are inherited through `Child` events so that a stolen task belongs to
+    /// the subgraph of the task which created it, not to the ones of the thief.
+    pub fn task_subgraph(&self) -> HashMap<TaskId, Option<SubGraphId>> {
+        self.tasks_subgraphs()
+            .into_iter()
+            .map(|(id, stack)| (id, stack.last().cloned()))
+            .collect()
+    }
+
+    /// Write all terminated tasks as a json array of
+    /// `{"thread", "start", "end", "label", "depth"}` objects, for lightweight timelines.
+    ///
+    /// Each task is resolved to its innermost subgraph (as in `task_subgraph`, so a stolen
+    /// task is in the subgraphs of the task which created it) whose label is given (`null`
+    /// outside of any subgraph) and `depth` is the number of subgraphs this one is nested
+    /// in (0 outside of any subgraph).
+    /// Tasks come thread by thread, in the order of their ends.
+    pub fn to_spans_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let subgraphs = self.tasks_subgraphs();
+        write!(out, "[")?;
+        let mut first = true;
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            for (id, start, end) in spans_of(events.iter()) {
+                let stack = subgraphs.get(&id).map(Vec::as_slice).unwrap_or_default();
+                if !first {
+                    write!(out, ",")?;
+                }
+                first = false;
+                write!(
+                    out,
+                    "{{\"thread\":{},\"start\":{},\"end\":{},\"label\":",
+                    thread, start, end
+                )?;
+                match stack.last().and_then(|label| self.label(*label)) {
+                    Some(label) => write_json_string(label, out)?,
+                    None => write!(out, "null")?,
+                }
+                write!(out, ",\"depth\":{}}}", stack.len().saturating_sub(1))?;
+            }
+        }
+        write!(out, "]")
+    }
+
+    /// Write a human readable listing of all events, thread by thread.
+    /// Times are relative to the first logged time and subgraphs nesting is shown
+    /// by indentation.
+    /// This is meant for debugging (use `write_to` for a machine readable output).
+    pub fn dump<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let start = self.time_bounds().map(|(start, _)| start).unwrap_or(0);
+        let label = |id: &SubGraphId| self.label(*id).unwrap_or("<unknown>");
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            writeln!(out, "thread {}:", thread)?;
+            let mut depth = 1;
+            for event in events {
+                if event.subgraph_work().is_some() {
+                    depth = (depth - 1).max(1);
+                }
+                write!(out, "{:width$}", "", width = 2 * depth)?;
+                match event {
+                    RawEvent::TaskStart(id, time) => {
+                        writeln!(out, "task {} starts at {}ns", id, time - start)?
+                    }
+                    RawEvent::TaskEnd(time) => writeln!(out, "task ends at {}ns", time - start)?,
+                    RawEvent::Child(id) => writeln!(out, "child {}", id)?,
+                    RawEvent::TaskMetric(value) => writeln!(out, "task metric {}", value)?,
+                    RawEvent::BlockStart(time) => {
+                        writeln!(out, "blocked at {}ns", time.saturating_sub(start))?
+                    }
+                    RawEvent::BlockEnd(time) => {
+                        writeln!(out, "unblocked at {}ns", time.saturating_sub(start))?
+                    }
+                    RawEvent::ParallelDepth(depth) => writeln!(out, "parallel depth {}", depth)?,
+                    RawEvent::MergedTasks(count) => writeln!(out, "{} merged tasks", count)?,
+                    RawEvent::TaskPriority(priority) => writeln!(out, "priority {}", priority)?,
+                    RawEvent::Marker(id, time) => writeln!(
+                        out,
+                        "marker {} at {}ns",
+                        label(id),
+                        time.saturating_sub(start)
+                    )?,
+                    RawEvent::PhaseStart(id, time) => writeln!(
+                        out,
+                        "phase {} starts at {}ns",
+                        label(id),
+                        time.saturating_sub(start)
+                    )?,
+                    RawEvent::Counter(id, value, time) => writeln!(
+                        out,
+                        "counter {} is {} at {}ns",
+                        label(id),
+                        value,
+                        time.saturating_sub(start)
+                    )?,
+                    RawEvent::SubgraphStart(id) => {
+                        writeln!(out, "subgraph {} starts", label(id))?;
+                        depth += 1;
+                    }
+                    RawEvent::SubgraphEnd(id, work) => {
+                        writeln!(out, "subgraph {} ends (work: {})", label(id), work)?
+                    }
+                    RawEvent::SubgraphEndF64(id, bits) => writeln!(
+                        out,
+                        "subgraph {} ends (work: {})",
+                        label(id),
+                        f64::from_bits(*bits)
+                    )?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
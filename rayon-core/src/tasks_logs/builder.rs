@@ -0,0 +1,97 @@
+//! Incremental construction of logs coming from outside of rayon.
+
+use super::{RawEvent, RawLogs, SubGraphId};
+use std::collections::HashMap;
+use std::io;
+
+/// Build `RawLogs` event by event from events carrying their labels as strings,
+/// for example when another process emits events.
+/// Labels are interned as events arrive, in order of first use.
+///
+/// ```
+/// use rayon_core::{RawEvent, RawLogsBuilder};
+///
+/// let mut builder = RawLogsBuilder::new();
+/// builder.push_event(0, RawEvent::TaskStart(0, 0)).unwrap();
+/// builder.push_event(0, RawEvent::SubgraphStart("load".to_string())).unwrap();
+/// builder.push_event(0, RawEvent::SubgraphEnd("load".to_string(), 1)).unwrap();
+/// builder.push_event(0, RawEvent::TaskEnd(10)).unwrap();
+/// let logs = builder.build();
+/// assert_eq!(logs.labels, vec!["load".to_string()]);
+/// ```
+#[derive(Debug, Default)]
+pub struct RawLogsBuilder {
+    thread_events: Vec<Vec<RawEvent<SubGraphId>>>,
+    labels: Vec<String>,
+    labels_ids: HashMap<String, SubGraphId>,
+}
+
+impl RawLogsBuilder {
+    /// Create a builder without any thread or label.
+    pub fn new() -> Self {
+        RawLogsBuilder::default()
+    }
+
+    /// Append an event to given thread, interning its label if any.
+    /// Threads are numbered in order of appearance: pushing to a thread above the number
+    /// of threads seen so far fails with `InvalidInput`, leaving the builder unchanged.
+    pub fn push_event(&mut self, thread: usize, event: RawEvent<String>) -> Result<(), io::Error> {
+        if thread > self.thread_events.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "thread {} pushed while only {} threads are known",
+                    thread,
+                    self.thread_events.len()
+                ),
+            ));
+        }
+        let event = match event {
+            RawEvent::TaskStart(id, time) => RawEvent::TaskStart(id, time),
+            RawEvent::TaskEnd(time) => RawEvent::TaskEnd(time),
+            RawEvent::Child(id) => RawEvent::Child(id),
+            RawEvent::TaskMetric(value) => RawEvent::TaskMetric(value),
+            RawEvent::BlockStart(time) => RawEvent::BlockStart(time),
+            RawEvent::BlockEnd(time) => RawEvent::BlockEnd(time),
+            RawEvent::ParallelDepth(depth) => RawEvent::ParallelDepth(depth),
+            RawEvent::MergedTasks(count) => RawEvent::MergedTasks(count),
+            RawEvent::TaskPriority(priority) => RawEvent::TaskPriority(priority),
+            RawEvent::SubgraphStart(label) => RawEvent::SubgraphStart(self.intern(label)),
+            RawEvent::SubgraphEnd(label, size) => RawEvent::SubgraphEnd(self.intern(label), size),
+            RawEvent::SubgraphEndF64(label, bits) => {
+                RawEvent::SubgraphEndF64(self.intern(label), bits)
+            }
+            RawEvent::Marker(label, time) => RawEvent::Marker(self.intern(label), time),
+            RawEvent::PhaseStart(label, time) => RawEvent::PhaseStart(self.intern(label), time),
+            RawEvent::Counter(label, value, time) => {
+                RawEvent::Counter(self.intern(label), value, time)
+            }
+        };
+        if thread == self.thread_events.len() {
+            self.thread_events.push(Vec::new());
+        }
+        self.thread_events[thread].push(event);
+        Ok(())
+    }
+
+    /// Return the id of given label, registering it if new.
+    fn intern(&mut self, label: String) -> SubGraphId {
+        let labels = &mut self.labels;
+        *self.labels_ids.entry(label).or_insert_with_key(|label| {
+            labels.push(label.clone());
+            labels.len() - 1
+        })
+    }
+
+    /// Return the logs built so far.
+    pub fn build(self) -> RawLogs {
+        RawLogs {
+            thread_events: self.thread_events,
+            units: vec![None; self.labels.len()],
+            labels: self.labels,
+            pool_config: None,
+            numa_nodes: Vec::new(),
+            provenance: None,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Export of logs in the Chrome trace event format.
+use super::analysis::{spans_of, write_json_string};
+use super::{RawLogs, TimeStamp};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// One complete ("X") event to export.
+struct TraceEvent {
+    name: String,
+    category: &'static str,
+    thread: usize,
+    start: TimeStamp,
+    end: TimeStamp,
+    work: Option<f64>,
+}
+
+impl RawLogs {
+    /// Write logs as a json array of Chrome trace events, which can be opened in
+    /// ui.perfetto.dev or chrome://tracing.
+    ///
+    /// Each terminated task and then each subgraph becomes a complete ("X") event on the
+    /// `tid` of its thread.
+    /// Tasks are named after their id and subgraphs after their label, with their work
+    /// amount in `args`.
+    /// Logs times are in nanoseconds while the format expects microseconds so all
+    /// timestamps and durations are divided by 1000 (keeping fractional parts).
+    pub fn write_chrome_trace<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "[")?;
+        for (index, event) in self.trace_events().iter().enumerate() {
+            if index != 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"name\":")?;
+            write_json_string(&event.name, out)?;
+            write!(
+                out,
+                ",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":{}",
+                event.category,
+                event.start as f64 / 1000.0,
+                event.end.saturating_sub(event.start) as f64 / 1000.0,
+                event.thread
+            )?;
+            match event.work {
+                Some(work) if work.is_finite() => write!(out, ",\"args\":{{\"work\":{}}}}}", work)?,
+                Some(_) => write!(out, ",\"args\":{{\"work\":null}}}}")?,
+                None => write!(out, "}}")?,
+            }
+        }
+        write!(out, "]")
+    }
+
+    /// Save logs in the Chrome trace event format to given file,
+    /// see `write_chrome_trace`.
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_chrome_trace(&mut file)?;
+        file.flush()
+    }
+
+    /// All terminated tasks, thread by thread, followed by all subgraphs.
+    fn trace_events(&self) -> Vec<TraceEvent> {
+        let tasks = self
+            .thread_events
+            .iter()
+            .enumerate()
+            .flat_map(|(thread, events)| {
+                spans_of(events.iter()).map(move |(id, start, end)| TraceEvent {
+                    name: format!("task {}", id),
+                    category: "task",
+                    thread,
+                    start,
+                    end,
+                    work: None,
+                })
+            });
+        let subgraphs = self.subgraph_spans().into_iter().map(|span| TraceEvent {
+            name: self.label(span.label).unwrap_or("<unknown>").to_string(),
+            category: "subgraph",
+            thread: span.thread,
+            start: span.start,
+            end: span.end,
+            work: span.work,
+        });
+        tasks.chain(subgraphs).collect()
+    }
+}
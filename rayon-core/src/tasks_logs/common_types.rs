@@ -3,14 +3,22 @@
 /// unique subgraph identifier
 pub type SubGraphId = usize;
 /// unique task identifier
-pub type TaskId = usize;
+/// (64 bits wide whatever the pointer width so that ids never wrap around)
+pub type TaskId = u64;
+/// Bit set in the ids of tasks logged with `log_external_task`.
+/// Ids handed out by rayon never have it set, so external ids cannot collide with them.
+pub const EXTERNAL_TASK_FLAG: TaskId = !(TaskId::MAX >> 1);
 /// at which time (in nanoseconds) does the event happen
 pub type TimeStamp = u64;
 
 /// All types of raw events we can log.
 /// It is generic because recorded logs and reloaded logs
 /// don't use the same strings for subgraphs.
-#[derive(Debug, Clone)]
+///
+/// `ParallelDepth`, `MergedTasks` and `TaskPriority` describe the active task: they are
+/// only logged when not at their default value (0, or 1 for `MergedTasks`), just after
+/// the task's start (see `RawLogs::per_task`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RawEvent<S> {
     /// A task starts.
     TaskStart(TaskId, TimeStamp),
@@ -18,17 +26,92 @@ pub enum RawEvent<S> {
     TaskEnd(TimeStamp),
     /// Direct link in the graph between two tasks (active one and given one).
     Child(TaskId),
+    /// Value measured by the pool's `task_metric` callback for the active task,
+    /// just before it ends.
+    TaskMetric(u64),
     /// Start a subgraph.
     SubgraphStart(S),
     /// End a subgraph and register a work amount.
     SubgraphEnd(S, usize),
+    /// End a subgraph and register a fractional work amount
+    /// (the bits of an `f64`, see `f64::from_bits`).
+    SubgraphEndF64(S, u64),
+    /// Active thread starts waiting for the other branch of a `join`
+    /// (stealing or sleeping).
+    BlockStart(TimeStamp),
+    /// Active thread stops waiting.
+    BlockEnd(TimeStamp),
+    /// Number of nested parallel iterators the active task runs in.
+    ParallelDepth(usize),
+    /// A named instant, see `Logger::mark`.
+    Marker(S, TimeStamp),
+    /// Number of original tasks the active task stands for in downsampled logs,
+    /// see `RawLogs::downsample`.
+    MergedTasks(usize),
+    /// Priority given by a scheduler to the active task, see `log_task_priority`.
+    TaskPriority(u8),
+    /// Start of a named phase of the program, see `Logger::begin_phase`.
+    PhaseStart(S, TimeStamp),
+    /// A named value measured at some time, see `tag_value`.
+    Counter(S, u64, TimeStamp),
 }
 
+/// An event whose subgraphs carry their label instead of its id,
+/// see `RawLogs::resolved_events`.
+pub type ResolvedEvent<'a> = RawEvent<&'a str>;
+
 /// Raw unprocessed logs. Very fast to record but require some postprocessing to be displayed.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct RawLogs {
     /// A vector containing for each thread a vector of all recorded events.
     pub thread_events: Vec<Vec<RawEvent<SubGraphId>>>,
     /// All labels used for tagging subgraphs.
     pub labels: Vec<String>,
+    /// For each label, the unit of its subgraphs work amounts (if any).
+    pub units: Vec<Option<String>>,
+    /// Configuration of the last logged pool.
+    pub(crate) pool_config: Option<PoolConfig>,
+    /// NUMA node of each thread (when captured).
+    pub(crate) numa_nodes: Vec<Option<usize>>,
+    /// Build of rayon which recorded the logs (if known).
+    pub(crate) provenance: Option<Provenance>,
+}
+
+/// Settings of a logged thread pool, captured when building it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolConfig {
+    /// Number of threads in the pool.
+    pub num_threads: usize,
+    /// Stack size of the worker threads, if specified.
+    pub stack_size: Option<usize>,
+    /// Did the workers execute spawned jobs in a breadth-first (fifo) fashion.
+    pub breadth_first: bool,
+}
+
+/// Build of rayon which recorded some logs, saved in logs files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Provenance {
+    /// Version of rayon-core.
+    pub version: String,
+    /// Enabled cargo features and cfg flags which can change the recorded events.
+    pub flags: Vec<String>,
+}
+
+impl Provenance {
+    /// Provenance of logs recorded by the running build.
+    pub(crate) fn current() -> Provenance {
+        let flags = [
+            ("debug_assertions", cfg!(debug_assertions)),
+            ("log", cfg!(feature = "log")),
+            ("otel", cfg!(feature = "otel")),
+        ];
+        Provenance {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            flags: flags
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(flag, _)| flag.to_string())
+                .collect(),
+        }
+    }
 }
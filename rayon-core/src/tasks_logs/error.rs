@@ -0,0 +1,134 @@
+//! Errors when saving or loading logs.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// An IO error while saving or loading logs, together with the part of the logs being
+/// written or read.
+#[derive(Debug)]
+pub enum LogError {
+    /// Failed creating or opening a file or writing or reading the header,
+    /// or read logs referencing unknown labels.
+    Io(io::Error),
+    /// Failed writing the labels table.
+    WritingLabels(io::Error),
+    /// Failed writing the number of threads.
+    WritingThreadCount(io::Error),
+    /// Failed writing the number of events of given thread.
+    WritingEventsCount {
+        /// Index of the thread.
+        thread: usize,
+        /// Underlying error.
+        source: io::Error,
+    },
+    /// Failed writing an event.
+    WritingEvent {
+        /// Index of the thread.
+        thread: usize,
+        /// Index of the event in its thread.
+        index: usize,
+        /// Underlying error.
+        source: io::Error,
+    },
+    /// Failed flushing the writer once all logs were written.
+    /// Buffered writers may only report here failures of previous parts.
+    Flushing(io::Error),
+    /// Failed reading the labels table.
+    ReadingLabels(io::Error),
+    /// Failed reading the number of threads.
+    ReadingThreadCount(io::Error),
+    /// Failed reading the number of events of given thread.
+    ReadingEventsCount {
+        /// Index of the thread.
+        thread: usize,
+        /// Underlying error.
+        source: io::Error,
+    },
+    /// Failed reading an event (or read an invalid one).
+    ReadingEvent {
+        /// Index of the thread.
+        thread: usize,
+        /// Index of the event in its thread.
+        index: usize,
+        /// Underlying error.
+        source: io::Error,
+    },
+}
+
+impl LogError {
+    /// The underlying IO error.
+    pub fn io_error(&self) -> &io::Error {
+        match self {
+            LogError::Io(e)
+            | LogError::WritingLabels(e)
+            | LogError::WritingThreadCount(e)
+            | LogError::Flushing(e)
+            | LogError::ReadingLabels(e)
+            | LogError::ReadingThreadCount(e) => e,
+            LogError::WritingEventsCount { source, .. }
+            | LogError::WritingEvent { source, .. }
+            | LogError::ReadingEventsCount { source, .. }
+            | LogError::ReadingEvent { source, .. } => source,
+        }
+    }
+}
+
+impl From<io::Error> for LogError {
+    fn from(error: io::Error) -> Self {
+        LogError::Io(error)
+    }
+}
+
+impl From<LogError> for io::Error {
+    fn from(error: LogError) -> Self {
+        io::Error::new(error.io_error().kind(), error)
+    }
+}
+
+impl Error for LogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.io_error())
+    }
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::Io(e) => write!(f, "logs input/output failed: {}", e),
+            LogError::WritingLabels(e) => write!(f, "writing labels failed: {}", e),
+            LogError::WritingThreadCount(e) => write!(f, "writing threads number failed: {}", e),
+            LogError::WritingEventsCount { thread, source } => write!(
+                f,
+                "writing events number of thread {} failed: {}",
+                thread, source
+            ),
+            LogError::WritingEvent {
+                thread,
+                index,
+                source,
+            } => write!(
+                f,
+                "writing event {} of thread {} failed: {}",
+                index, thread, source
+            ),
+            LogError::Flushing(e) => write!(f, "flushing logs failed: {}", e),
+            LogError::ReadingLabels(e) => write!(f, "reading labels failed: {}", e),
+            LogError::ReadingThreadCount(e) => write!(f, "reading threads number failed: {}", e),
+            LogError::ReadingEventsCount { thread, source } => write!(
+                f,
+                "reading events number of thread {} failed: {}",
+                thread, source
+            ),
+            LogError::ReadingEvent {
+                thread,
+                index,
+                source,
+            } => write!(
+                f,
+                "reading event {} of thread {} failed: {}",
+                index, thread, source
+            ),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Labels of `join` branches, logged as subgraphs (see `ThreadPoolBuilder::log_joins`).
+
+use super::LOG_JOINS;
+use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::Location;
+use std::sync::Mutex;
+
+/// Labels of both branches of a `join` call site.
+type JoinLabels = (&'static str, &'static str);
+
+/// File, line and column of a `join` call site.
+type JoinSite = (&'static str, u32, u32);
+
+lazy_static! {
+    /// Labels of all logged `join` call sites.
+    static ref JOIN_LABELS: Mutex<HashMap<JoinSite, JoinLabels>> = Mutex::new(HashMap::new());
+}
+
+thread_local! {
+    /// Labels of the `join` call sites already logged by this thread.
+    static THREAD_JOIN_LABELS: RefCell<HashMap<JoinSite, JoinLabels>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Return the labels of both branches of the `join` at given location
+/// if the current thread logs joins.
+/// Labels are created once per call site and live forever.
+/// Each thread caches the labels it uses so that the global table is only locked the
+/// first time a thread meets a call site.
+pub(crate) fn join_labels(location: &'static Location<'static>) -> Option<JoinLabels> {
+    if !LOG_JOINS.with(|l| l.get()) {
+        return None;
+    }
+    let key = (location.file(), location.line(), location.column());
+    let labels = THREAD_JOIN_LABELS.with(|cache| {
+        if let Some(labels) = cache.borrow().get(&key) {
+            return *labels;
+        }
+        let labels = *JOIN_LABELS.lock().unwrap().entry(key).or_insert_with(|| {
+            let leak = |branch| -> &'static str {
+                Box::leak(format!("join {} at {}", branch, location).into_boxed_str())
+            };
+            (leak("a"), leak("b"))
+        });
+        cache.borrow_mut().insert(key, labels);
+        labels
+    });
+    Some(labels)
+}
@@ -1,59 +1,576 @@
 //! Main public structure for accesses to logs.
 
-use super::log;
-use super::next_task_id;
-use super::now;
+use super::analysis::{durations_of, span_of};
 use super::storage::Storage;
-use super::{RawEvent, RawLogs, SubGraphId, TaskId};
+use super::wire_format::{
+    is_truncation, read_header, read_provenance, read_u64, read_varint, read_vec_strings_from,
+    vec_strings_len, write_header, write_provenance, write_u64, write_varint, write_vec_strings_to,
+    FORMAT_COMPACT, FORMAT_FIXED, HEADER_LEN, PROVENANCE_MARK,
+};
+use super::{detach_current_thread, now, time_of};
+use super::{next_generation, thread_storage, ThreadStorage};
+use super::{record, EventSink, LogError, ManualClock};
+use super::{PoolConfig, Provenance, RawEvent, RawLogs, SubGraphId, TimeStamp};
 use std::collections::HashMap;
 use std::collections::LinkedList;
 use std::fs::File;
 use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
 
 /// This is the main structure for logging in rayon.
-#[derive(Debug)]
+///
+/// Cloning a `Logger` is cheap and all clones share the same recording:
+/// pools built from any clone are logged together and any clone can extract the logs.
+#[derive(Debug, Clone)]
 pub struct Logger {
     /// All logs are registered here.
-    logs: Arc<Mutex<LinkedList<Arc<Storage<RawEvent<&'static str>>>>>>,
+    logs: Arc<SharedLogs>,
+}
+
+/// Everything shared between a `Logger` and the pools it logs.
+pub(crate) struct SharedLogs {
+    /// Logs of each registered thread.
+    threads: Mutex<LinkedList<ThreadLogs>>,
+    /// How many events we pre-allocate for each thread.
+    capacity: usize,
+    /// Settings of the last logged pool.
+    pool_config: Mutex<Option<PoolConfig>>,
+    /// Our generation, isolating us from other loggers using the same threads.
+    generation: u64,
+    /// Label of the main task (logged as a subgraph around it).
+    main_label: Option<&'static str>,
+    /// Callback receiving events instead of the storages.
+    sink: Option<Arc<EventSink>>,
+    /// Clock replacing the real time.
+    clock: Option<Arc<ManualClock>>,
+    /// Next task id (0 is the main task).
+    task_ids: Arc<AtomicU64>,
+    /// Events starting the main task after a reset, which go before the events of the
+    /// thread which created us.
+    /// They are recorded by whichever thread resets us so they cannot go in the storage
+    /// of the main thread (each storage has a single writer).
+    main_task: Mutex<Vec<RawEvent<&'static str>>>,
+    /// Number of events the sink could not forward.
+    dropped_events: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for SharedLogs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedLogs")
+            .field("threads", &self.threads)
+            .field("capacity", &self.capacity)
+            .field("pool_config", &self.pool_config)
+            .field("generation", &self.generation)
+            .field("main_label", &self.main_label)
+            .field("sink", &self.sink.as_ref().map(|_| "<closure>"))
+            .field("clock", &self.clock)
+            .field("task_ids", &self.task_ids)
+            .field("main_task", &self.main_task)
+            .field("dropped_events", &self.dropped_events)
+            .finish()
+    }
+}
+
+/// Logs of one registered thread.
+#[derive(Debug)]
+struct ThreadLogs {
+    storage: Arc<ThreadStorage>,
+    /// Index of the thread in its pool when registering (see `current_thread_index`).
+    pool_index: Option<usize>,
+    /// NUMA node the thread ran on when registering (if captured and known).
+    numa_node: Option<usize>,
+}
+
+/// Events recorded in place by the thread of given index in the logger's threads, after
+/// given main task's events for the first thread (the one which created the logger).
+fn recorded_events<'a>(
+    index: usize,
+    thread: &'a ThreadLogs,
+    main_task: &'a [RawEvent<&'static str>],
+) -> impl Iterator<Item = &'a RawEvent<&'static str>> + 'a {
+    let main_task = if index == 0 { main_task } else { &[] };
+    main_task
+        .iter()
+        .chain(thread.storage.events.iter().map(|event| -> &'a _ { event }))
+}
+
+/// Return the NUMA node of the cpu the current thread is running on, if known.
+/// This is only available on linux, where we look up the current cpu in `/proc` and its
+/// node in `/sys`.
+fn current_numa_node() -> Option<usize> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    // the processor is the 39th field, after the command which may contain spaces
+    let stat = std::fs::read_to_string("/proc/thread-self/stat").ok()?;
+    let cpu: usize = stat
+        .rsplit(')')
+        .next()?
+        .split_whitespace()
+        .nth(36)?
+        .parse()
+        .ok()?;
+    std::fs::read_dir(format!("/sys/devices/system/cpu/cpu{}", cpu))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()
+        })
+}
+
+impl SharedLogs {
+    /// Direct events of the current thread to us and return its storage.
+    fn attach_current_thread(&self) -> Arc<ThreadStorage> {
+        let storage = thread_storage(self.generation, || ThreadStorage {
+            sink: self.sink.clone(),
+            clock: self.clock.clone(),
+            task_ids: self.task_ids.clone(),
+            ..Default::default()
+        });
+        storage.events.reserve(self.capacity);
+        storage
+    }
+    /// Current time on our clock, whichever thread calls this.
+    fn now(&self) -> TimeStamp {
+        time_of(self.clock.as_deref())
+    }
+    /// Start logging the current thread, capturing its NUMA node if asked to.
+    pub(crate) fn register_current_thread(&self, capture_numa_node: bool) {
+        let storage = self.attach_current_thread();
+        let numa_node = if capture_numa_node {
+            current_numa_node()
+        } else {
+            None
+        };
+        self.threads.lock().unwrap().push_back(ThreadLogs {
+            storage,
+            pool_index: crate::current_thread_index(),
+            numa_node,
+        })
+    }
+    /// Remember the settings of a pool we are about to log.
+    pub(crate) fn record_pool_config(&self, config: PoolConfig) {
+        *self.pool_config.lock().unwrap() = Some(config);
+    }
+}
+
+impl Drop for SharedLogs {
+    /// Detach all our threads, which stop passing events to our sink or timing them with
+    /// our clock: the current thread right away and others on their next event.
+    fn drop(&mut self) {
+        if let Ok(threads) = self.threads.get_mut() {
+            for thread in threads.iter() {
+                thread.storage.detached.store(true, Ordering::Relaxed);
+            }
+        }
+        detach_current_thread();
+    }
 }
 
 impl Logger {
     /// Create a new global logger.
     /// The thread calling this method will get logged in addition
     /// to all threads obtained from `pool_builder` method.
+    ///
+    /// Loggers are isolated from each other: a thread only records events for the last
+    /// logger it was attached to (by creating it or by belonging to one of its pools).
     pub fn new() -> Self {
-        let logs = Arc::new(Mutex::new(LinkedList::new()));
+        Logger::with_capacity(0)
+    }
+    /// Create a new global logger, pre-allocating space for `events_per_thread` events
+    /// in each logged thread.
+    /// This avoids any allocation during recording as long as no thread records more events,
+    /// removing some noise in timing sensitive benchmarks.
+    pub fn with_capacity(events_per_thread: usize) -> Self {
+        Logger::build(events_per_thread, None, None, None, Default::default())
+    }
+    /// Create a new global logger whose main task (the one of the calling thread) is
+    /// labeled with given name.
+    /// The label is logged as a subgraph around the main task so that the top level of
+    /// all timelines is named.
+    pub fn new_labeled(name: &'static str) -> Self {
+        Logger::build(0, Some(name), None, None, Default::default())
+    }
+    /// Create a new global logger passing each event to given callback as soon as it is
+    /// logged, instead of buffering it.
+    /// This allows forwarding events to a custom sink (a channel, a ring buffer,...) with
+    /// no buffering on rayon's side: extracted logs will contain no events.
+    ///
+    /// The callback runs on the worker threads, in the middle of the logged computation,
+    /// so it must be fast and should not block: any time spent in it shows in the logs.
+    pub fn with_sink<F>(sink: F) -> Self
+    where
+        F: Fn(&RawEvent<&'static str>) + Send + Sync + 'static,
+    {
+        Logger::build(0, None, Some(Arc::new(sink)), None, Default::default())
+    }
+    /// Create a new global logger sending each event, with the index in its pool of the
+    /// thread logging it, down given channel (for example to stream events to a remote
+    /// profiler from another thread).
+    ///
+    /// Sending never blocks: events which do not fit in the channel (or sent after the
+    /// receiver is gone) are dropped and counted, see `dropped_events`.
+    ///
+    /// ```
+    /// use rayon_core::{Logger, RawEvent};
+    /// use std::sync::mpsc::sync_channel;
+    ///
+    /// let (sender, receiver) = sync_channel(1_000);
+    /// let logger = Logger::with_channel(sender);
+    /// let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    /// pool.install(|| rayon_core::subgraph("work", 1, || ()));
+    /// drop(pool);
+    /// drop(logger);
+    /// let subgraphs = receiver
+    ///     .try_iter()
+    ///     .filter(|(_, event)| *event == RawEvent::SubgraphStart("work"))
+    ///     .count();
+    /// assert_eq!(subgraphs, 1);
+    /// ```
+    pub fn with_channel(sender: SyncSender<(Option<usize>, RawEvent<&'static str>)>) -> Self {
+        let dropped_events = Arc::new(AtomicU64::new(0));
+        let dropped = dropped_events.clone();
+        let sink = move |event: &RawEvent<&'static str>| {
+            if sender
+                .try_send((crate::current_thread_index(), event.clone()))
+                .is_err()
+            {
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        };
+        Logger::build(0, None, Some(Arc::new(sink)), None, dropped_events)
+    }
+    /// Number of events dropped so far because the channel was full or disconnected
+    /// (always 0 for loggers not created with `with_channel`).
+    pub fn dropped_events(&self) -> u64 {
+        self.logs.dropped_events.load(Ordering::Relaxed)
+    }
+    /// Create a new global logger timing events with given clock instead of the real time.
+    /// Combined with `single_thread_pool_builder` this produces fully reproducible
+    /// timestamps and tasks ids (which are numbered per logger), for testing code
+    /// analyzing logs.
+    ///
+    /// ```
+    /// use rayon_core::{Logger, ManualClock, RawEvent};
+    /// use std::sync::Arc;
+    ///
+    /// let clock = Arc::new(ManualClock::new());
+    /// let logger = Logger::with_manual_clock(clock.clone());
+    /// let pool = logger.single_thread_pool_builder().build().unwrap();
+    /// pool.install(|| rayon_core::join(|| clock.advance(10), || clock.advance(5)));
+    /// let logs = logger.extract_logs();
+    /// let times: Vec<_> = logs.thread_events[1].iter().filter_map(RawEvent::time).collect();
+    /// assert_eq!(times, vec![0, 0, 10, 10, 15, 15]);
+    /// ```
+    pub fn with_manual_clock(clock: Arc<ManualClock>) -> Self {
+        Logger::build(0, None, None, Some(clock), Default::default())
+    }
+    fn build(
+        events_per_thread: usize,
+        main_label: Option<&'static str>,
+        sink: Option<Arc<EventSink>>,
+        clock: Option<Arc<ManualClock>>,
+        dropped_events: Arc<AtomicU64>,
+    ) -> Self {
+        let logs = Arc::new(SharedLogs {
+            threads: Mutex::new(LinkedList::new()),
+            capacity: events_per_thread,
+            pool_config: Mutex::new(None),
+            generation: next_generation(),
+            main_label,
+            sink,
+            clock,
+            task_ids: Arc::new(AtomicU64::new(1)),
+            main_task: Mutex::new(Vec::new()),
+            dropped_events,
+        });
         {
-            logs.lock()
-                .unwrap()
-                .push_front(super::THREAD_LOGS.with(|l| {
-                    l.push(RawEvent::TaskStart(0, now()));
-                    l.clone()
-                }));
+            let storage = logs.attach_current_thread();
+            record(RawEvent::TaskStart(0, now()));
+            if let Some(label) = main_label {
+                record(RawEvent::SubgraphStart(label));
+            }
+            logs.threads.lock().unwrap().push_front(ThreadLogs {
+                storage,
+                pool_index: crate::current_thread_index(),
+                numa_node: None,
+            });
         }
         Logger { logs }
     }
+    /// Start a new main task (after a reset) in the logs of the thread which created us,
+    /// whichever thread calls this.
+    fn start_main_task(&self) {
+        let id = self.logs.task_ids.fetch_add(1, Ordering::SeqCst);
+        let mut events = vec![RawEvent::TaskStart(id, self.logs.now())];
+        if let Some(label) = self.logs.main_label {
+            events.push(RawEvent::SubgraphStart(label));
+        }
+        match &self.logs.sink {
+            Some(sink) => events.iter().for_each(|event| sink(event)),
+            None => *self.logs.main_task.lock().unwrap() = events,
+        }
+    }
+    /// Globally turn logging on or off at runtime (it is on by default).
+    /// While disabled, events are dropped instead of being recorded, for all loggers.
+    /// Toggling while tasks are running may leave some of their tasks or subgraphs
+    /// unterminated in the logs.
+    pub fn set_enabled(enabled: bool) {
+        super::set_logging_enabled(enabled)
+    }
+    /// Record a named instant on the current thread, for example to delimit the steady
+    /// state of a benchmark (see `RawLogs::markers_bounds`).
+    /// Marker names are stored alongside subgraph labels.
+    pub fn mark(name: &'static str) {
+        subgraph_logs!(RawEvent::Marker(name, now()))
+    }
+    /// Start a new named phase of the program (for example a stage of a pipeline),
+    /// ending the previous one, see `RawLogs::phases`.
+    /// Unlike `reset` this keeps all logs so a single extraction covers all phases.
+    pub fn begin_phase(name: &'static str) {
+        subgraph_logs!(RawEvent::PhaseStart(name, now()))
+    }
+    /// Total number of events currently recorded in all logged threads.
+    /// This only reads counters so it is cheap and can be polled while threads record.
+    pub fn events_count(&self) -> usize {
+        let threads = self.logs.threads.lock().unwrap();
+        let main_task = self.logs.main_task.lock().unwrap().len();
+        main_task
+            + threads
+                .iter()
+                .map(|t| t.storage.events.len())
+                .sum::<usize>()
+    }
+    /// Total number of events all logged threads can hold without allocating
+    /// (including the recorded ones).
+    /// Multiply by `size_of::<RawEvent<&str>>()` for an estimate of the memory used.
+    pub fn events_capacity(&self) -> usize {
+        self.logs
+            .threads
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| t.storage.events.capacity())
+            .sum()
+    }
+    /// Number of currently open (started but not yet ended) subgraphs of each label in
+    /// all logged threads.
+    /// Like `subgraph_count` this reads the logs in place and scans all recorded events,
+    /// it is meant to be polled periodically for a live view of what the pool is doing.
+    pub fn live_label_activity(&self) -> HashMap<String, usize> {
+        let mut activity = HashMap::new();
+        let threads = self.logs.threads.lock().unwrap();
+        let main_task = self.logs.main_task.lock().unwrap();
+        for (index, thread) in threads.iter().enumerate() {
+            let mut open_subgraphs = Vec::new();
+            for event in recorded_events(index, thread, &main_task) {
+                match event {
+                    RawEvent::SubgraphStart(label) => open_subgraphs.push(*label),
+                    RawEvent::SubgraphEnd(..) | RawEvent::SubgraphEndF64(..) => {
+                        open_subgraphs.pop();
+                    }
+                    _ => (),
+                }
+            }
+            for label in open_subgraphs {
+                *activity.entry(label.to_string()).or_insert(0) += 1;
+            }
+        }
+        activity
+    }
+    /// Number of subgraphs with given label started so far in all logged threads.
+    /// This reads the logs in place, without extracting them, but it scans all recorded
+    /// events so it is O(events).
+    pub fn subgraph_count(&self, label: &str) -> usize {
+        let threads = self.logs.threads.lock().unwrap();
+        let main_task = self.logs.main_task.lock().unwrap();
+        threads
+            .iter()
+            .enumerate()
+            .flat_map(|(index, thread)| recorded_events(index, thread, &main_task))
+            .filter(|event| matches!(event, RawEvent::SubgraphStart(l) if *l == label))
+            .count()
+    }
+    /// Sum of the durations of all tasks terminated so far.
+    /// This reads the logs in place, without extracting them.
+    pub fn total_work(&self) -> TimeStamp {
+        self.logs
+            .threads
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|t| durations_of(t.storage.events.iter()))
+            .map(|(_, duration)| duration)
+            .sum()
+    }
+    /// Estimate the fraction (in percents) of the tasks work spent recording events.
+    ///
+    /// The cost of one event is measured by recording some events in a scratch storage
+    /// and multiplied by the number of recorded events, so this is only approximate.
+    /// A high value means subgraphs (or tasks) are so fine grained that logging dominates
+    /// and timings are not representative.
+    pub fn overhead_estimate(&self) -> f64 {
+        const SAMPLES: u64 = 1_000;
+        let scratch = Storage::new();
+        let start = now();
+        for _ in 0..SAMPLES {
+            scratch.push(RawEvent::<&'static str>::TaskEnd(now()));
+        }
+        let event_cost = (now() - start) as f64 / SAMPLES as f64;
+        let work = self.total_work();
+        if work == 0 {
+            0.0
+        } else {
+            (100.0 * event_cost * self.events_count() as f64 / work as f64).min(100.0)
+        }
+    }
+    /// Duration of the longest chain of dependent tasks terminated so far.
+    /// This reads the logs in place, without extracting them.
+    pub fn current_span(&self) -> TimeStamp {
+        span_of(
+            self.logs
+                .threads
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|t| t.storage.events.iter()),
+        )
+    }
     /// Create a `ThreadPoolBuilder` whose pool will be logged.
     pub fn pool_builder(&self) -> crate::ThreadPoolBuilder {
         let mut builder: crate::ThreadPoolBuilder = Default::default();
         builder.tasks_logger = Some(self.logs.clone());
         builder
     }
+    /// Create a `ThreadPoolBuilder` for a logged pool of a single thread, whose logs
+    /// only depend on the program when using a `ManualClock`.
+    pub fn single_thread_pool_builder(&self) -> crate::ThreadPoolBuilder {
+        self.pool_builder().num_threads(1)
+    }
     /// Extract recorded logs (removing them from records).
+    ///
+    /// This blocks while a logged pool is registering its threads so it should not be
+    /// called from a pool's worker during the pool's creation.
     pub fn extract_logs(&self) -> RawLogs {
         RawLogs::new(self)
     }
+    /// Extract recorded logs without ever blocking.
+    /// Return `None` if the logs are currently locked (by a pool registering its threads,
+    /// a pool being configured or another extraction).
+    /// This is safe to call from anywhere, including a pool's worker in a middle
+    /// of a parallel region (for example from a timer in a service).
+    ///
+    /// Nothing is recorded on the calling thread: the main task is only closed in the
+    /// extracted logs.
+    pub fn try_extract_logs(&self) -> Option<RawLogs> {
+        fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+            match mutex.try_lock() {
+                Ok(guard) => Some(guard),
+                Err(TryLockError::WouldBlock) => None,
+                Err(TryLockError::Poisoned(e)) => panic!("{}", e),
+            }
+        }
+        let threads = try_lock(&self.logs.threads)?;
+        let pool_config = try_lock(&self.logs.pool_config)?.clone();
+        let main_task = try_lock(&self.logs.main_task)?;
+        let mut logs = RawLogs::from_threads(threads.iter(), &main_task, pool_config);
+        self.close_main_task(&mut logs);
+        Some(logs)
+    }
+    /// Close the main task (and its label) at the end of the first thread of given logs,
+    /// without recording anything.
+    /// The main task stays open in the recorded logs, so that successive extractions
+    /// all contain it.
+    fn close_main_task(&self, logs: &mut RawLogs) {
+        if self.logs.sink.is_some() {
+            // the main task was not stored
+            return;
+        }
+        let main_label = self.logs.main_label.and_then(|label| logs.label_id(label));
+        if let Some(main_thread) = logs.thread_events.first_mut() {
+            if let Some(label) = main_label {
+                main_thread.push(RawEvent::SubgraphEnd(label, 1));
+            }
+            main_thread.push(RawEvent::TaskEnd(self.logs.now()));
+        }
+    }
+    /// Extract and erase the logs of the logged thread of given index in its pool, leaving
+    /// other threads untouched.
+    /// Indices are the ones returned by `current_thread_index` on the logged threads, so
+    /// `None` stands for the thread which created the logger (outside of any pool).
+    /// The events come in a `RawLogs` with a single thread since they need a labels table
+    /// (or with one thread per pool, in registration order, if several logged pools have
+    /// a thread with this index).
+    /// Return `None` if there is no such thread.
+    ///
+    /// Like `reset` this should only be called when the flushed thread is not recording
+    /// any event.
+    pub fn flush_thread(&self, pool_index: Option<usize>) -> Option<RawLogs> {
+        let threads = self.logs.threads.lock().unwrap();
+        let flushed: Vec<&ThreadLogs> = threads
+            .iter()
+            .filter(|thread| thread.pool_index == pool_index)
+            .collect();
+        if flushed.is_empty() {
+            return None;
+        }
+        let pool_config = self.logs.pool_config.lock().unwrap().clone();
+        // the main task goes with the thread which created us
+        let mut main_task = self.logs.main_task.lock().unwrap();
+        let flushes_main_thread =
+            matches!(threads.front(), Some(main_thread) if std::ptr::eq(flushed[0], main_thread));
+        let main_task_events: &[_] = if flushes_main_thread { &main_task } else { &[] };
+        let logs = RawLogs::from_threads(flushed.iter().cloned(), main_task_events, pool_config);
+        if flushes_main_thread {
+            main_task.clear();
+        }
+        for thread in flushed {
+            thread.storage.events.reset();
+            thread.storage.events.reserve(self.logs.capacity);
+        }
+        Some(logs)
+    }
+    /// Extract recorded logs and erase them in a single step, for rotating logs.
+    ///
+    /// No pool can register threads between the extraction and the reset, so that no
+    /// thread is missed.
+    /// Like `reset` this should be called between parallel regions, when no thread is
+    /// recording events (events recorded concurrently might be lost).
+    pub fn take_logs(&self) -> RawLogs {
+        let threads = self.logs.threads.lock().unwrap();
+        let pool_config = self.logs.pool_config.lock().unwrap().clone();
+        let main_task = std::mem::take(&mut *self.logs.main_task.lock().unwrap());
+        let mut logs = RawLogs::from_threads(threads.iter(), &main_task, pool_config);
+        self.close_main_task(&mut logs);
+        threads.iter().for_each(|thread| {
+            thread.storage.events.reset();
+            thread.storage.events.reserve(self.logs.capacity);
+        });
+        self.start_main_task();
+        logs
+    }
     /// Erase all logs and restart logging.
     pub fn reset(&self) {
-        self.logs.lock().unwrap().iter().for_each(|log| log.reset());
-        log(RawEvent::TaskStart(next_task_id(), now()));
+        let threads = self.logs.threads.lock().unwrap();
+        threads.iter().for_each(|thread| {
+            thread.storage.events.reset();
+            thread.storage.events.reserve(self.logs.capacity);
+        });
+        self.start_main_task();
     }
 
     /// Save log file of currently recorded raw logs.
     /// This will reset logs.
-    pub fn save_raw_logs<P: AsRef<Path>>(&mut self, path: P) -> Result<(), io::Error> {
+    pub fn save_raw_logs<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LogError> {
         let logs = RawLogs::new(self);
         logs.save(path)?;
         self.reset();
@@ -67,20 +584,52 @@ impl RawLogs {
     /// We are able to extract logs during recording but the obtained logs
     /// might be incomplete.
     pub(crate) fn new(logger: &Logger) -> Self {
-        // stop main task
-        log(RawEvent::TaskEnd(now()));
+        let threads = logger.logs.threads.lock().unwrap();
+        let pool_config = logger.logs.pool_config.lock().unwrap().clone();
+        let main_task = logger.logs.main_task.lock().unwrap();
+        let mut logs = RawLogs::from_threads(threads.iter(), &main_task, pool_config);
+        logger.close_main_task(&mut logs);
+        logs
+    }
+
+    /// Extract events from given threads logs, recorded with given pool configuration.
+    /// `main_task` events go before the events of the first thread.
+    fn from_threads<'a>(
+        threads: impl Iterator<Item = &'a ThreadLogs>,
+        main_task: &[RawEvent<&'static str>],
+        pool_config: Option<PoolConfig>,
+    ) -> Self {
         // associate a unique integer id to each label
         let mut next_label_count = 0;
         let mut seen_labels = HashMap::new();
         let mut labels = Vec::new();
         let mut thread_events: Vec<Vec<RawEvent<SubGraphId>>> = Vec::new();
+        let mut numa_nodes = Vec::new();
+        // unit of each label, `None` if threads registered different units for it
+        let mut labels_units: HashMap<&str, Option<&str>> = HashMap::new();
         // loop on all logged  rayon events per thread
-        for thread_logs in logger.logs.lock().unwrap().iter() {
+        for (index, thread_logs) in threads.enumerate() {
+            numa_nodes.push(thread_logs.numa_node);
+            for (label, unit) in thread_logs.storage.units.lock().unwrap().iter() {
+                labels_units
+                    .entry(*label)
+                    .and_modify(|known_unit| {
+                        if *known_unit != Some(*unit) {
+                            *known_unit = None
+                        }
+                    })
+                    .or_insert(Some(*unit));
+            }
             let mut events = Vec::new();
-            for rayon_event in thread_logs.iter() {
+            for rayon_event in recorded_events(index, thread_logs, main_task) {
                 // store eventual event label
                 match rayon_event {
-                    RawEvent::SubgraphStart(label) | RawEvent::SubgraphEnd(label, _) => {
+                    RawEvent::SubgraphStart(label)
+                    | RawEvent::SubgraphEnd(label, _)
+                    | RawEvent::SubgraphEndF64(label, _)
+                    | RawEvent::Marker(label, _)
+                    | RawEvent::PhaseStart(label, _)
+                    | RawEvent::Counter(label, ..) => {
                         seen_labels.entry(*label).or_insert_with(|| {
                             let label_count = next_label_count;
                             next_label_count += 1;
@@ -97,92 +646,473 @@ impl RawLogs {
             thread_events.push(events);
         }
 
+        // keep logs without any known node comparable to loaded ones
+        if numa_nodes.iter().all(Option::is_none) {
+            numa_nodes.clear();
+        }
+        let units = labels
+            .iter()
+            .map(|label| {
+                labels_units
+                    .get(label.as_str())
+                    .cloned()
+                    .flatten()
+                    .map(|unit| unit.to_string())
+            })
+            .collect();
         // now we just need to turn the hash table into a vector, filling the gaps
         // if some threads registered no events yet
         RawLogs {
             thread_events,
             labels,
+            units,
+            pool_config,
+            numa_nodes,
+            provenance: Some(Provenance::current()),
         }
     }
-    fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
-        let mut file = File::create(path)?;
-        // we start by saving all labels
-        write_vec_strings_to(&self.labels, &mut file)?;
+
+    /// Build logs from given events and labels, for example for tests or for logs
+    /// coming from another source.
+    /// Fails with `InvalidData` if an event references a label outside of `labels`.
+    pub fn from_parts(
+        thread_events: Vec<Vec<RawEvent<SubGraphId>>>,
+        labels: Vec<String>,
+    ) -> Result<RawLogs, io::Error> {
+        check_labels(&thread_events, labels.len())?;
+        Ok(RawLogs {
+            thread_events,
+            units: vec![None; labels.len()],
+            labels,
+            pool_config: None,
+            numa_nodes: Vec::new(),
+            provenance: None,
+        })
+    }
+
+    /// Insert a timed event (see `RawEvent::time`) in given thread, for example to overlay
+    /// pauses measured outside of rayon on the timeline.
+    /// The event goes after all events of the thread at or before its time, found by
+    /// binary search.
+    /// Fails with `InvalidInput` for unknown threads or labels, untimed events and threads
+    /// whose events are not sorted by time.
+    pub fn insert_event(
+        &mut self,
+        thread: usize,
+        event: RawEvent<SubGraphId>,
+    ) -> Result<(), io::Error> {
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        let threads_number = self.thread_events.len();
+        let events = match self.thread_events.get_mut(thread) {
+            Some(events) => events,
+            None => return invalid(format!("unknown thread {} of {}", thread, threads_number)),
+        };
+        let time = match event.time() {
+            Some(time) => time,
+            None => return invalid(format!("cannot insert untimed event {:?}", event)),
+        };
+        if let RawEvent::Marker(label, _)
+        | RawEvent::PhaseStart(label, _)
+        | RawEvent::Counter(label, ..) = event
+        {
+            if label >= self.labels.len() {
+                return invalid(format!("unknown label {}", label));
+            }
+        }
+        // the insertion is linear anyway so we can afford checking the order
+        let times: Vec<TimeStamp> = events.iter().filter_map(RawEvent::time).collect();
+        if times.windows(2).any(|pair| pair[0] > pair[1]) {
+            return invalid(format!("events of thread {} are not sorted", thread));
+        }
+        // untimed events take the time of the last timed event before them
+        let time_at = |index: usize| events[..=index].iter().rev().find_map(RawEvent::time);
+        let (mut low, mut high) = (0, events.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            if !matches!(time_at(middle), Some(t) if t > time) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        events.insert(low, event);
+        Ok(())
+    }
+
+    /// NUMA node on which given thread was when registering, if it was captured
+    /// (see `ThreadPoolBuilder::log_numa_nodes`) and available.
+    /// Like the pool's configuration this is not saved in logs files and is always `None`
+    /// for loaded logs.
+    pub fn numa_node(&self, thread: usize) -> Option<usize> {
+        self.numa_nodes.get(thread).cloned().flatten()
+    }
+
+    /// Version and build flags of rayon which recorded the logs.
+    /// This is saved in logs files (but not by `save_partitioned`) and is `None` for
+    /// logs not coming from rayon or loaded from older files.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    /// Settings of the logged pool (or of the last one if several pools were logged).
+    /// This is not saved in logs files and is always `None` for loaded logs.
+    pub fn pool_config(&self) -> Option<&PoolConfig> {
+        self.pool_config.as_ref()
+    }
+    fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), LogError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_to(&mut file)
+    }
+
+    /// Write logs (in the same format as saved files) to given writer.
+    /// This allows sending logs to a socket, a buffer or a compression stream.
+    /// The writer is flushed once, after the last event.
+    pub fn write_to<W: Write>(&self, destination: &mut W) -> Result<(), LogError> {
+        write_header(FORMAT_FIXED, destination)?;
+        write_provenance(self.provenance.as_ref(), destination)?;
+        // we start by saving all labels and their units
+        self.write_labels_to(destination)
+            .map_err(LogError::WritingLabels)?;
         // write the number of threads
-        write_u64(self.thread_events.len() as u64, &mut file)?;
+        write_u64(self.thread_events.len() as u64, destination)
+            .map_err(LogError::WritingThreadCount)?;
         // now, all events
-        for events in &self.thread_events {
-            write_u64(events.len() as u64, &mut file)?; // how many events for this thread
-            events.iter().try_for_each(|e| e.write_to(&mut file))?;
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            write_thread_events(thread, events, destination, write_u64, |e, destination| {
+                e.write_to(destination)
+            })?;
+        }
+        destination.flush().map_err(LogError::Flushing)
+    }
+
+    /// Total number of events of all threads.
+    pub fn event_count(&self) -> usize {
+        self.thread_events.iter().map(Vec::len).sum()
+    }
+
+    /// Exact number of bytes written by `write_to` (and saved in files), for example to
+    /// preallocate a buffer.
+    pub fn encoded_size(&self) -> usize {
+        let provenance = PROVENANCE_MARK.len()
+            + vec_strings_len(self.provenance.iter().flat_map(|provenance| {
+                std::iter::once(provenance.version.as_str())
+                    .chain(provenance.flags.iter().map(String::as_str))
+            }));
+        let labels = vec_strings_len(self.labels.iter().map(String::as_str));
+        let units = vec_strings_len(
+            self.units
+                .iter()
+                .map(|unit| unit.as_ref().map_or("", String::as_str)),
+        );
+        let events: usize = self
+            .thread_events
+            .iter()
+            .map(|events| 8 + events.iter().map(RawEvent::encoded_len).sum::<usize>())
+            .sum();
+        HEADER_LEN + provenance + labels + units + 8 + events
+    }
+
+    /// Save logs in given directory (created if needed), with one file for each thread.
+    /// `labels.rlog` holds labels, units and the number of threads and each thread's
+    /// events go in `thread_<n>.rlog`.
+    /// Use `load_partitioned` to reassemble them.
+    pub fn save_partitioned<P: AsRef<Path>>(&self, dir: P) -> Result<(), LogError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let mut file = BufWriter::new(File::create(dir.join("labels.rlog"))?);
+        self.write_labels_to(&mut file)
+            .map_err(LogError::WritingLabels)?;
+        write_u64(self.thread_events.len() as u64, &mut file)
+            .map_err(LogError::WritingThreadCount)?;
+        file.flush().map_err(LogError::Flushing)?;
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            let path = dir.join(format!("thread_{}.rlog", thread));
+            let mut file = BufWriter::new(File::create(path)?);
+            write_thread_events(thread, events, &mut file, write_u64, |e, destination| {
+                e.write_to(destination)
+            })?;
+            file.flush().map_err(LogError::Flushing)?;
+        }
+        Ok(())
+    }
+
+    /// Write logs in the compact format to given writer.
+    /// Integers are written as varints and times are delta-encoded, typically producing
+    /// files several times smaller than `write_to`.
+    /// Both formats are read by `load_lenient`.
+    pub fn write_compact_to<W: Write>(&self, destination: &mut W) -> Result<(), LogError> {
+        write_header(FORMAT_COMPACT, destination)?;
+        write_provenance(self.provenance.as_ref(), destination)?;
+        self.write_labels_to(destination)
+            .map_err(LogError::WritingLabels)?;
+        write_varint(self.thread_events.len() as u64, destination)
+            .map_err(LogError::WritingThreadCount)?;
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            let mut previous_time = 0;
+            write_thread_events(
+                thread,
+                events,
+                destination,
+                write_varint,
+                |e, destination| e.write_compact_to(&mut previous_time, destination),
+            )?;
+        }
+        destination.flush().map_err(LogError::Flushing)
+    }
+
+    /// Load logs saved with `save_partitioned` in given directory.
+    pub fn load_partitioned<P: AsRef<Path>>(dir: P) -> Result<RawLogs, LogError> {
+        let dir = dir.as_ref();
+        let mut file = BufReader::new(File::open(dir.join("labels.rlog"))?);
+        let (labels, units) = read_labels_from(&mut file).map_err(LogError::ReadingLabels)?;
+        let threads_number = read_u64(&mut file).map_err(LogError::ReadingThreadCount)?;
+        let thread_events: Vec<_> = (0..threads_number as usize)
+            .map(|thread| {
+                let path = dir.join(format!("thread_{}.rlog", thread));
+                let mut file = BufReader::new(File::open(path)?);
+                let events_number = read_u64(&mut file)
+                    .map_err(|source| LogError::ReadingEventsCount { thread, source })?;
+                (0..events_number as usize)
+                    .map(|index| {
+                        RawEvent::read_from(&mut file).map_err(|source| LogError::ReadingEvent {
+                            thread,
+                            index,
+                            source,
+                        })
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, LogError>>()?;
+        check_labels(&thread_events, labels.len())?;
+        Ok(RawLogs {
+            thread_events,
+            labels,
+            units,
+            pool_config: None,
+            numa_nodes: Vec::new(),
+            provenance: None,
+        })
+    }
+
+    /// Write labels and their units.
+    fn write_labels_to<W: Write>(&self, destination: &mut W) -> Result<(), io::Error> {
+        write_vec_strings_to(&self.labels, destination)?;
+        // empty for no unit
+        let units: Vec<String> = self
+            .units
+            .iter()
+            .map(|unit| unit.clone().unwrap_or_default())
+            .collect();
+        write_vec_strings_to(&units, destination)
+    }
+
+    /// Load raw logs saved with `Logger::save_raw_logs` (in any format) from given file.
+    /// Truncated files fail with `InvalidData`, see `load_lenient` to recover them.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RawLogs, LogError> {
+        RawLogs::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Like `load` but read logs from given source.
+    pub fn read_from<R: BufRead>(source: &mut R) -> Result<RawLogs, LogError> {
+        let (logs, truncated) = RawLogs::read_lenient_from(source)?;
+        if truncated {
+            Err(LogError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated logs file",
+            )))
+        } else {
+            Ok(logs)
+        }
+    }
+
+    /// Load raw logs from given file, recovering as many complete events as possible.
+    /// This is useful for post-mortem analysis when a program crashed while saving.
+    /// Loading stops cleanly at the first truncated record and we return all events read
+    /// so far together with a boolean indicating if the file was truncated.
+    pub fn load_lenient<P: AsRef<Path>>(path: P) -> Result<(RawLogs, bool), LogError> {
+        RawLogs::read_lenient_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Like `load_lenient` but read logs from given source.
+    /// Malformed input (unknown tags or formats, invalid strings or references to unknown
+    /// labels) fails with `InvalidData`: this never panics whatever the input bytes.
+    pub fn read_lenient_from<R: BufRead>(source: &mut R) -> Result<(RawLogs, bool), LogError> {
+        // files without header have no provenance
+        let (format, provenance) = match read_header(source)? {
+            Some(format) => (format, read_provenance(source)?),
+            None => (FORMAT_FIXED, None),
+        };
+        let (labels, units) = read_labels_from(source).map_err(LogError::ReadingLabels)?;
+        let mut logs = RawLogs {
+            thread_events: Vec::new(),
+            labels,
+            units,
+            pool_config: None,
+            numa_nodes: Vec::new(),
+            provenance,
+        };
+        let truncated = match logs.read_lenient_events(source, format == FORMAT_COMPACT) {
+            Ok(()) => false,
+            Err(ref e) if is_truncation(e.io_error()) => true,
+            Err(e) => return Err(e),
+        };
+        check_labels(&logs.thread_events, logs.labels.len())?;
+        Ok((logs, truncated))
+    }
+
+    /// Read all threads events (in the compact format or not),
+    /// keeping everything we could read on error.
+    fn read_lenient_events<R: Read>(
+        &mut self,
+        source: &mut R,
+        compact: bool,
+    ) -> Result<(), LogError> {
+        let read_number = if compact { read_varint } else { read_u64 };
+        let threads_number = read_number(source).map_err(LogError::ReadingThreadCount)?;
+        for thread in 0..threads_number as usize {
+            let events_number = read_number(source)
+                .map_err(|source| LogError::ReadingEventsCount { thread, source })?;
+            self.thread_events.push(Vec::new());
+            let events = self.thread_events.last_mut().unwrap();
+            let mut previous_time = 0;
+            for index in 0..events_number as usize {
+                let event = if compact {
+                    RawEvent::read_compact_from(&mut previous_time, source)
+                } else {
+                    RawEvent::read_from(source)
+                };
+                events.push(event.map_err(|source| LogError::ReadingEvent {
+                    thread,
+                    index,
+                    source,
+                })?);
+            }
         }
         Ok(())
     }
 }
 
-// little endian write
-fn write_u64<W: std::io::Write>(integer: u64, destination: &mut W) -> std::io::Result<()> {
-    let mut remaining = integer;
-    for _ in 0..8 {
-        let low_bits = (remaining & 255) as u8;
-        remaining = remaining >> 8;
-        destination.write(&[low_bits])?;
+/// Write the number of events of given thread followed by its events,
+/// locating failures with a `LogError`.
+fn write_thread_events<W: Write>(
+    thread: usize,
+    events: &[RawEvent<SubGraphId>],
+    destination: &mut W,
+    write_number: fn(u64, &mut W) -> Result<(), io::Error>,
+    mut write_event: impl FnMut(&RawEvent<SubGraphId>, &mut W) -> Result<(), io::Error>,
+) -> Result<(), LogError> {
+    write_number(events.len() as u64, destination)
+        .map_err(|source| LogError::WritingEventsCount { thread, source })?;
+    for (index, event) in events.iter().enumerate() {
+        write_event(event, destination).map_err(|source| LogError::WritingEvent {
+            thread,
+            index,
+            source,
+        })?;
     }
     Ok(())
 }
 
-fn write_vec_strings_to<W: std::io::Write>(
-    vector: &Vec<String>,
-    destination: &mut W,
-) -> std::io::Result<()> {
-    // write the length
-    write_u64(vector.len() as u64, destination)?;
-    // write for each string its byte size and then all bytes
-    for string in vector {
-        let bytes = string.as_bytes();
-        write_u64(string.len() as u64, destination)?;
-        destination.write(bytes)?;
+/// Fail with `InvalidData` if an event references a label outside of the `labels_number`
+/// first ones.
+fn check_labels(
+    thread_events: &[Vec<RawEvent<SubGraphId>>],
+    labels_number: usize,
+) -> Result<(), io::Error> {
+    for (thread, events) in thread_events.iter().enumerate() {
+        for (index, event) in events.iter().enumerate() {
+            match event {
+                RawEvent::SubgraphStart(label)
+                | RawEvent::SubgraphEnd(label, _)
+                | RawEvent::SubgraphEndF64(label, _)
+                | RawEvent::Marker(label, _)
+                | RawEvent::PhaseStart(label, _)
+                | RawEvent::Counter(label, ..)
+                    if *label >= labels_number =>
+                {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "unknown label {} in event {} of thread {}",
+                            label, index, thread
+                        ),
+                    ));
+                }
+                _ => (),
+            }
+        }
     }
     Ok(())
 }
 
-impl RawEvent<TaskId> {
+/// Read labels and their units.
+fn read_labels_from<R: Read>(
+    source: &mut R,
+) -> Result<(Vec<String>, Vec<Option<String>>), io::Error> {
+    let labels = read_vec_strings_from(source)?;
+    let units = read_vec_strings_from(source)?
+        .into_iter()
+        .map(|unit| if unit.is_empty() { None } else { Some(unit) })
+        .collect();
+    Ok((labels, units))
+}
+
+impl RawEvent<SubGraphId> {
     pub(crate) fn new(
         rayon_event: &RawEvent<&'static str>,
         strings: &HashMap<&str, usize>,
-    ) -> RawEvent<TaskId> {
+    ) -> RawEvent<SubGraphId> {
         match rayon_event {
             RawEvent::TaskStart(id, time) => RawEvent::TaskStart(*id, *time),
             RawEvent::TaskEnd(time) => RawEvent::TaskEnd(*time),
             RawEvent::Child(id) => RawEvent::Child(*id),
+            RawEvent::TaskMetric(value) => RawEvent::TaskMetric(*value),
+            RawEvent::BlockStart(time) => RawEvent::BlockStart(*time),
+            RawEvent::BlockEnd(time) => RawEvent::BlockEnd(*time),
+            RawEvent::ParallelDepth(depth) => RawEvent::ParallelDepth(*depth),
+            RawEvent::MergedTasks(count) => RawEvent::MergedTasks(*count),
+            RawEvent::TaskPriority(priority) => RawEvent::TaskPriority(*priority),
+            RawEvent::Marker(label, time) => RawEvent::Marker(strings[label], *time),
+            RawEvent::PhaseStart(label, time) => RawEvent::PhaseStart(strings[label], *time),
+            RawEvent::Counter(label, value, time) => {
+                RawEvent::Counter(strings[label], *value, *time)
+            }
             RawEvent::SubgraphStart(label) => RawEvent::SubgraphStart(strings[label]),
             RawEvent::SubgraphEnd(label, size) => RawEvent::SubgraphEnd(strings[label], *size),
-        }
-    }
-    pub(crate) fn write_to<W: std::io::Write>(&self, destination: &mut W) -> std::io::Result<()> {
-        match self {
-            RawEvent::TaskStart(id, time) => {
-                destination.write(&[2u8])?;
-                write_u64(*id as u64, destination)?;
-                write_u64(*time, destination)?;
-            }
-            RawEvent::TaskEnd(time) => {
-                destination.write(&[3u8])?;
-                write_u64(*time, destination)?;
-            }
-            RawEvent::Child(id) => {
-                destination.write(&[4u8])?;
-                write_u64(*id as u64, destination)?;
-            }
-            RawEvent::SubgraphStart(label) => {
-                destination.write(&[5u8])?;
-                write_u64(*label as u64, destination)?;
-            }
-            RawEvent::SubgraphEnd(label, size) => {
-                destination.write(&[6u8])?;
-                write_u64(*label as u64, destination)?;
-                write_u64(*size as u64, destination)?;
+            RawEvent::SubgraphEndF64(label, bits) => {
+                RawEvent::SubgraphEndF64(strings[label], *bits)
             }
         }
-        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn try_extract_logs_does_not_block() {
+        let logger = Logger::new();
+        let pool = logger.pool_builder().num_threads(2).build().unwrap();
+        let logs = pool.install(|| crate::subgraph("region", 1, || logger.try_extract_logs()));
+        assert!(logs.is_some());
+        let registering = logger.logs.threads.lock().unwrap();
+        assert!(pool.install(|| logger.try_extract_logs()).is_none());
+        drop(registering);
+        let _configuring = logger.logs.pool_config.lock().unwrap();
+        assert!(pool.install(|| logger.try_extract_logs()).is_none());
+    }
+
+    #[test]
+    fn try_extract_logs_records_nothing() {
+        let logger = Logger::new();
+        let pool = logger.pool_builder().num_threads(1).build().unwrap();
+        pool.install(|| crate::subgraph("region", 1, || logger.try_extract_logs()));
+        let logs = logger.extract_logs();
+        // the worker only recorded the subgraph's tasks
+        let count = |kind: fn(&RawEvent<SubGraphId>) -> bool| {
+            logs.thread_events[1].iter().filter(|e| kind(e)).count()
+        };
+        assert_eq!(count(|e| matches!(e, RawEvent::TaskStart(..))), 2);
+        assert_eq!(count(|e| matches!(e, RawEvent::TaskEnd(_))), 2);
     }
 }
@@ -4,22 +4,24 @@
 //! - calling logs functions for each operation implying tasks (join, install, spawn, scope,...)
 
 mod common_types;
-pub use common_types::{RawEvent, RawLogs, SubGraphId, TaskId, TimeStamp};
+pub use common_types::{
+    PoolConfig, Provenance, RawEvent, RawLogs, ResolvedEvent, SubGraphId, TaskId, TimeStamp,
+    EXTERNAL_TASK_FLAG,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex,
 };
 
-// tasks ids data and function
-// ---------------------------
-
-/// We use an atomic usize to generate unique ids for tasks.
-/// We start at 1 since initial task (0) is created manually.
-static NEXT_TASK_ID: AtomicUsize = AtomicUsize::new(1);
+// tasks ids function
+// ------------------
 
-/// get an id for a new task and increment global tasks counter.
+/// get an id for a new task and increment the tasks counter of the logger the current
+/// thread belongs to (see `ThreadStorage::task_ids`).
 pub(super) fn next_task_id() -> TaskId {
-    NEXT_TASK_ID.fetch_add(1, Ordering::SeqCst)
+    THREAD_LOGS.with(|l| l.borrow().task_ids.fetch_add(1, Ordering::SeqCst))
 }
 
 // timing data and function
@@ -30,46 +32,365 @@ lazy_static! {
     static ref START_TIME: std::time::Instant = std::time::Instant::now();
 }
 
-/// Return number of nano seconds since start.
+/// Return number of nano seconds since start, or the time of the manual clock of the
+/// current thread's logger if it has one.
 pub(super) fn now() -> TimeStamp {
-    START_TIME.elapsed().as_nanos() as TimeStamp
+    THREAD_LOGS.with(|l| time_of(l.borrow().clock.as_deref()))
+}
+
+/// Return the time of given clock, or number of nano seconds since start if none.
+pub(super) fn time_of(clock: Option<&ManualClock>) -> TimeStamp {
+    clock.map_or_else(
+        || START_TIME.elapsed().as_nanos() as TimeStamp,
+        ManualClock::now,
+    )
+}
+
+/// A clock only advancing on explicit calls, making logs reproducible in tests
+/// (see `Logger::with_manual_clock`).
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    time: AtomicU64,
+}
+
+impl ManualClock {
+    /// Create a clock at time 0.
+    pub fn new() -> Self {
+        ManualClock::default()
+    }
+    /// Move the clock forward by given number of nanoseconds.
+    pub fn advance(&self, nanoseconds: TimeStamp) {
+        self.time.fetch_add(nanoseconds, Ordering::SeqCst);
+    }
+    /// Current time of the clock.
+    pub fn now(&self) -> TimeStamp {
+        self.time.load(Ordering::SeqCst)
+    }
 }
 
 // logging data and functions
 // --------------------------
 
+/// Global switch for all logging, see `Logger::set_enabled`.
+static LOGGING_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Is logging currently enabled at all.
+/// This is checked before recording any event so it needs to stay cheap.
+#[inline]
+pub(super) fn logging_enabled() -> bool {
+    LOGGING_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Turn all logging on or off.
+pub(super) fn set_logging_enabled(enabled: bool) {
+    LOGGING_ENABLED.store(enabled, Ordering::Relaxed)
+}
+
+/// Each `Logger` gets a unique generation (0 stands for no logger).
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Get a generation for a new logger.
+pub(super) fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Callback receiving each event instead of storing it, see `Logger::with_sink`.
+pub(super) type EventSink = dyn Fn(&RawEvent<&'static str>) + Send + Sync;
+
+/// Everything a thread records for the logger it belongs to, and how.
+#[derive(Default)]
+pub(super) struct ThreadStorage {
+    /// recorded events
+    pub(super) events: Storage<RawEvent<&'static str>>,
+    /// unit of work registered on this thread for each label
+    /// (see `custom_subgraph_with_unit`)
+    pub(super) units: Mutex<HashMap<&'static str, &'static str>>,
+    /// where events go instead of `events` (set by the logger we belong to)
+    pub(super) sink: Option<Arc<EventSink>>,
+    /// clock of the logger we belong to, if it does not use the real time
+    pub(super) clock: Option<Arc<ManualClock>>,
+    /// next task id of the logger we belong to (shared by all its threads)
+    pub(super) task_ids: Arc<AtomicU64>,
+    /// set once the logger we belong to is dropped
+    pub(super) detached: AtomicBool,
+}
+
+impl std::fmt::Debug for ThreadStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadStorage")
+            .field("events", &self.events)
+            .field("units", &self.units)
+            .field("sink", &self.sink.as_ref().map(|_| "<closure>"))
+            .field("clock", &self.clock)
+            .field("task_ids", &self.task_ids)
+            .field("detached", &self.detached)
+            .finish()
+    }
+}
+
 thread_local! {
     /// each thread has a storage space for logs
-    pub(super) static THREAD_LOGS: Arc<Storage<RawEvent<&'static str>>> =  {
-        Arc::new(Storage::new())
+    pub(super) static THREAD_LOGS: RefCell<Arc<ThreadStorage>> =  {
+        RefCell::new(Arc::new(ThreadStorage::default()))
     };
+    /// generation of the logger the thread's storage belongs to
+    static THREAD_GENERATION: Cell<u64> = const { Cell::new(0) };
+    /// do we log automatic tasks events on this thread (set by the pool we belong to)
+    pub(super) static LOG_TASKS: Cell<bool> = const { Cell::new(true) };
+    /// do we log join branches as subgraphs on this thread (set by the pool we belong to)
+    pub(super) static LOG_JOINS: Cell<bool> = const { Cell::new(false) };
+    /// do we only log outermost subgraphs on this thread (set by the pool we belong to)
+    pub(super) static LOG_ROOT_SUBGRAPHS_ONLY: Cell<bool> = const { Cell::new(false) };
+    /// number of nested parallel iterators the current task runs in (only maintained while
+    /// logging)
+    pub(super) static PARALLEL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// number of subgraphs the current task runs in (see `log_root_subgraphs_only`)
+    pub(super) static SUBGRAPHS_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// are tasks events of the current task skipped (see `without_tasks_logs`)
+    static TASKS_LOGS_SUPPRESSED: Cell<bool> = const { Cell::new(false) };
+    /// measure to record at the end of each task on this thread (set by the pool we belong to)
+    pub(super) static TASK_METRIC: RefCell<Option<Arc<crate::TaskMetric>>> =
+        const { RefCell::new(None) };
+}
+
+/// Return current thread's storage space for logger of given generation.
+/// If the storage belongs to another generation, the thread switches to the storage built
+/// by `new_storage`: an older logger will not see any of the events recorded from now on.
+pub(super) fn thread_storage(
+    generation: u64,
+    new_storage: impl FnOnce() -> ThreadStorage,
+) -> Arc<ThreadStorage> {
+    let previous_generation = THREAD_GENERATION.with(|g| g.replace(generation));
+    THREAD_LOGS.with(|l| {
+        if previous_generation != generation {
+            *l.borrow_mut() = Arc::new(new_storage());
+        }
+        l.borrow().clone()
+    })
+}
+
+/// Switch to a storage of no logger if the logger of given storage is gone,
+/// dropping the logger's sink and clock.
+fn detach_if_orphaned(storage: &RefCell<Arc<ThreadStorage>>) {
+    if storage.borrow().detached.load(Ordering::Relaxed) {
+        *storage.borrow_mut() = Arc::default();
+        THREAD_GENERATION.with(|g| g.set(0));
+    }
+}
+
+/// Detach current thread from its logger if that one is gone.
+pub(super) fn detach_current_thread() {
+    // the storage is already gone if the thread is exiting
+    let _ = THREAD_LOGS.try_with(detach_if_orphaned);
+}
+
+/// Run `op` on current thread's storage (detaching it first if its logger is gone).
+pub(super) fn with_thread_storage<R>(op: impl FnOnce(&ThreadStorage) -> R) -> R {
+    THREAD_LOGS.with(|l| {
+        detach_if_orphaned(l);
+        op(&l.borrow())
+    })
+}
+
+/// Are automatic tasks events (from join, scope,...) logged on this thread.
+pub(super) fn logging_tasks() -> bool {
+    LOG_TASKS.with(|l| l.get()) && !TASKS_LOGS_SUPPRESSED.with(|s| s.get())
+}
+
+/// Run `op` without logging any tasks event, neither on this thread nor in the tasks
+/// it spawns (wherever they run).
+pub(super) fn without_tasks_logs<R>(op: impl FnOnce() -> R) -> R {
+    TaskContext {
+        tasks_logs_suppressed: true,
+        ..TaskContext::current()
+    }
+    .run(op)
+}
+
+/// Store given event in logs of current thread, or pass it to the logger's sink if any.
+pub(super) fn record(event: RawEvent<&'static str>) {
+    with_thread_storage(|storage| match &storage.sink {
+        Some(sink) => sink(&event),
+        None => storage.events.push(event),
+    })
 }
 
-/// Add given event to logs of current thread.
+/// Add given tasks event to logs of current thread.
+/// This is a no-op if the pool only logs subgraphs.
 pub(super) fn log(event: RawEvent<&'static str>) {
-    THREAD_LOGS.with(|l| l.push(event))
+    if logging_enabled() && logging_tasks() {
+        record(event)
+    }
+}
+
+/// Log the parallel depth of the task which just started on this thread, if not 0.
+pub(super) fn log_parallel_depth() {
+    let depth = PARALLEL_DEPTH.with(|d| d.get());
+    if depth != 0 {
+        log(RawEvent::ParallelDepth(depth))
+    }
+}
+
+/// Logging state of a task, carried to whichever thread executes its children.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct TaskContext {
+    subgraphs_depth: usize,
+    parallel_depth: usize,
+    tasks_logs_suppressed: bool,
+}
+
+impl TaskContext {
+    /// Context of the task running on the current thread.
+    pub(super) fn current() -> Self {
+        TaskContext {
+            subgraphs_depth: SUBGRAPHS_DEPTH.with(|d| d.get()),
+            parallel_depth: PARALLEL_DEPTH.with(|d| d.get()),
+            tasks_logs_suppressed: TASKS_LOGS_SUPPRESSED.with(|s| s.get()),
+        }
+    }
+    /// Run `op` in this context on current thread, restoring the previous context after
+    /// (even if `op` panics).
+    pub(super) fn run<R>(self, op: impl FnOnce() -> R) -> R {
+        let _restore = self.enter();
+        op()
+    }
+    /// Switch current thread to this context until the returned guard is dropped.
+    pub(super) fn enter(self) -> ContextGuard {
+        let previous = ContextGuard(TaskContext::current());
+        self.install();
+        previous
+    }
+    fn install(self) {
+        SUBGRAPHS_DEPTH.with(|d| d.set(self.subgraphs_depth));
+        PARALLEL_DEPTH.with(|d| d.set(self.parallel_depth));
+        TASKS_LOGS_SUPPRESSED.with(|s| s.set(self.tasks_logs_suppressed));
+    }
+}
+
+/// Restores the context it holds when dropped, see `TaskContext::enter`.
+#[derive(Debug)]
+pub(super) struct ContextGuard(TaskContext);
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        self.0.install();
+    }
+}
+
+/// Keeps the current thread one parallel iterator deeper while alive,
+/// see `RawEvent::ParallelDepth`.
+/// This is used by rayon's iterators bridges and is a no-op while logging is disabled or
+/// if the pool only logs subgraphs.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct ParallelRegion(bool);
+
+impl ParallelRegion {
+    /// Enter a parallel iterator.
+    pub fn enter() -> Self {
+        let active = logging_enabled() && logging_tasks();
+        if active {
+            PARALLEL_DEPTH.with(|d| d.set(d.get() + 1));
+        }
+        ParallelRegion(active)
+    }
 }
 
-/// Logs several events at once (with decreased cost).
+impl Drop for ParallelRegion {
+    fn drop(&mut self) {
+        if self.0 {
+            PARALLEL_DEPTH.with(|d| d.set(d.get().saturating_sub(1)))
+        }
+    }
+}
+
+/// Record the pool's task metric (if any) for the active task, which is about to end.
+/// This is a no-op if the pool only logs subgraphs.
+pub(super) fn log_task_metric() {
+    if logging_tasks() {
+        TASK_METRIC.with(|m| {
+            if let Some(metric) = &*m.borrow() {
+                log(RawEvent::TaskMetric(metric()))
+            }
+        })
+    }
+}
+
+/// Logs several tasks events at once (with decreased cost).
+/// This is a no-op if the pool only logs subgraphs.
 macro_rules! logs {
     ($($x:expr ), +) => {
-        $crate::tasks_logs::THREAD_LOGS.with(|l| {
-            $(
-                l.push($x);
-              )*
-        })
+        if $crate::tasks_logs::logging_tasks() {
+            subgraph_logs!($($x),+)
+        }
+    }
+}
+
+/// Logs several events at once, even if the pool only logs subgraphs.
+/// This is a no-op if logging is globally disabled.
+macro_rules! subgraph_logs {
+    ($($x:expr ), +) => {
+        if $crate::tasks_logs::logging_enabled() {
+            $crate::tasks_logs::with_thread_storage(|storage| match &storage.sink {
+                Some(sink) => {
+                    $(
+                        sink(&$x);
+                      )*
+                }
+                None => {
+                    $(
+                        storage.events.push($x);
+                      )*
+                }
+            })
+        }
     }
 }
 
 // define and re-export subgraphs functions
 mod subgraphs;
-pub use subgraphs::{custom_subgraph, subgraph};
+pub use subgraphs::{
+    custom_subgraph, custom_subgraph_f64, custom_subgraph_with_unit, log_external_task,
+    log_task_priority, sampled_subgraph, subgraph, tag_value,
+};
+
+// labels of logged `join` branches
+#[cfg(feature = "join_labels")]
+mod join_labels;
+#[cfg(feature = "join_labels")]
+pub(super) use join_labels::join_labels;
 
 // define and re-export `Storage` structure
 mod list;
 mod storage;
 pub(super) use storage::Storage;
 
+// errors when saving logs
+mod error;
+pub use error::LogError;
+
+// binary format of logs files
+pub mod wire_format;
+
+// post-processing functions on `RawLogs`
+mod analysis;
+pub use analysis::{
+    ActiveState, EventKinds, GrainReport, LogStatistics, PhaseStatistics, SubgraphMetric,
+    SubgraphStats, TaskTree, ThreadStatistics,
+};
+// export to the Chrome trace event format
+mod chrome_trace;
+#[cfg(feature = "otel")]
+mod otel;
+
 // define and re-export the main public structure : `Logger`
 pub mod logger;
 pub use logger::Logger;
+
+// building logs from events of other sources
+mod builder;
+pub use builder::RawLogsBuilder;
+pub(super) use logger::SharedLogs;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,118 @@
+//! Export of logs as OpenTelemetry spans.
+use super::analysis::write_json_string;
+use super::{RawEvent, RawLogs, TaskId, TimeStamp};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// All spans belong to the same trace.
+const TRACE_ID: &str = "00000000000000000000000000000001";
+
+/// One span to export.
+struct Span {
+    id: u64,
+    parent: Option<u64>,
+    name: String,
+    thread: usize,
+    start: TimeStamp,
+    end: TimeStamp,
+}
+
+impl RawLogs {
+    /// Write logs as an OTLP/JSON trace (an `ExportTraceServiceRequest`), ready to be sent
+    /// to an OpenTelemetry collector.
+    ///
+    /// Each terminated task and each subgraph becomes a span.
+    /// Tasks spans are named after their task id and their parent span is the task which
+    /// created them (the first one for tasks with several parents).
+    /// Subgraphs spans are named after their label.
+    /// Times are in nanoseconds since the start of the logs, not since the unix epoch.
+    pub fn to_otlp<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let spans = self.otel_spans();
+        write!(
+            out,
+            "{{\"resourceSpans\":[{{\"resource\":{{\"attributes\":[{{\"key\":\"service.name\",\
+             \"value\":{{\"stringValue\":\"rayon\"}}}}]}},\"scopeSpans\":[{{\"scope\":\
+             {{\"name\":\"rayon-core\"}},\"spans\":["
+        )?;
+        for (index, span) in spans.iter().enumerate() {
+            if index != 0 {
+                write!(out, ",")?;
+            }
+            write!(
+                out,
+                "{{\"traceId\":\"{}\",\"spanId\":\"{:016x}\",",
+                TRACE_ID, span.id
+            )?;
+            if let Some(parent) = span.parent {
+                write!(out, "\"parentSpanId\":\"{:016x}\",", parent)?;
+            }
+            write!(out, "\"name\":")?;
+            write_json_string(&span.name, out)?;
+            write!(
+                out,
+                ",\"kind\":1,\"startTimeUnixNano\":\"{}\",\"endTimeUnixNano\":\"{}\",\
+                 \"attributes\":[{{\"key\":\"thread\",\"value\":{{\"intValue\":\"{}\"}}}}]}}",
+                span.start, span.end, span.thread
+            )?;
+        }
+        write!(out, "]}}]}}]}}")
+    }
+
+    /// Compute all spans with unique (non-zero) ids.
+    fn otel_spans(&self) -> Vec<Span> {
+        let mut parents: HashMap<TaskId, TaskId> = HashMap::new();
+        let mut tasks = Vec::new();
+        for (thread, events) in self.thread_events.iter().enumerate() {
+            let mut active_tasks = Vec::new();
+            for event in events {
+                match event {
+                    RawEvent::TaskStart(id, start) => active_tasks.push((*id, *start)),
+                    RawEvent::TaskEnd(end) => {
+                        if let Some((id, start)) = active_tasks.pop() {
+                            tasks.push((thread, id, start, *end))
+                        }
+                    }
+                    RawEvent::Child(child) => {
+                        if let Some((id, _)) = active_tasks.last() {
+                            parents.entry(*child).or_insert(*id);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let spans_ids: HashMap<TaskId, u64> = tasks
+            .iter()
+            .enumerate()
+            .map(|(index, &(_, id, _, _))| (id, index as u64 + 1))
+            .collect();
+        let mut spans: Vec<Span> = tasks
+            .iter()
+            .map(|&(thread, id, start, end)| Span {
+                id: spans_ids[&id],
+                parent: parents
+                    .get(&id)
+                    .and_then(|parent| spans_ids.get(parent).cloned()),
+                name: format!("task {}", id),
+                thread,
+                start,
+                end,
+            })
+            .collect();
+        let first_subgraph_id = spans.len() as u64 + 1;
+        spans.extend(
+            self.subgraph_spans()
+                .into_iter()
+                .enumerate()
+                .map(|(index, span)| Span {
+                    id: first_subgraph_id + index as u64,
+                    parent: None,
+                    name: self.label(span.label).unwrap_or("<unknown>").to_string(),
+                    thread: span.thread,
+                    start: span.start,
+                    end: span.end,
+                }),
+        );
+        spans
+    }
+}
@@ -1,6 +1,7 @@
 //! provides a `Storage` structure with O(1) WORST CASE very fast insertions.
 //! every thread has its own storage and will be the only one to write in it.
 use super::list::AtomicLinkedList;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 const BLOCK_SIZE: usize = 10_000;
 
@@ -20,20 +21,35 @@ impl<T> Default for Block<T> {
 impl<T> Block<T> {
     /// Create a new block.
     fn new() -> Self {
+        Block::with_capacity(BLOCK_SIZE)
+    }
+
+    /// Create a new block able to hold `capacity` elements.
+    fn with_capacity(capacity: usize) -> Self {
         Block {
-            data: Vec::with_capacity(BLOCK_SIZE),
+            data: Vec::with_capacity(capacity),
         }
     }
 
     /// Add given element to block.
     fn push(&mut self, element: T) {
-        debug_assert!(self.data.len() != BLOCK_SIZE);
+        debug_assert!(!self.is_full());
         self.data.push(element)
     }
 
     /// Is there some space left.
     fn is_full(&self) -> bool {
-        self.data.len() == BLOCK_SIZE
+        self.data.len() == self.data.capacity()
+    }
+
+    /// How many elements can we still push.
+    fn remaining_space(&self) -> usize {
+        self.data.capacity() - self.data.len()
+    }
+
+    /// Number of elements we can hold.
+    fn capacity(&self) -> usize {
+        self.data.capacity()
     }
 
     /// Iterator on all elements.
@@ -47,6 +63,10 @@ impl<T> Block<T> {
 #[derive(Debug)]
 pub(crate) struct Storage<T> {
     data: AtomicLinkedList<Block<T>>,
+    /// Number of elements stored, readable while the owning thread pushes.
+    len: AtomicUsize,
+    /// Number of elements we can hold, readable while the owning thread pushes.
+    capacity: AtomicUsize,
 }
 
 unsafe impl<T: Sync> Sync for Storage<T> {}
@@ -61,9 +81,14 @@ impl<T: 'static> Storage<T> {
     /// Create a new storage space.
     pub(super) fn new() -> Self {
         let first_block = Block::new();
+        let capacity = AtomicUsize::new(first_block.capacity());
         let list = AtomicLinkedList::new();
         list.push_front(first_block);
-        Storage { data: list }
+        Storage {
+            data: list,
+            len: AtomicUsize::new(0),
+            capacity,
+        }
     }
 
     /// Add given element to storage space.
@@ -72,14 +97,39 @@ impl<T: 'static> Storage<T> {
     pub(crate) fn push(&self, element: T) {
         let space_needed = self.data.front().unwrap().is_full();
         if space_needed {
-            self.data.push_front(Block::new());
+            self.push_block(Block::new());
+        }
+        self.data.front_mut().unwrap().push(element);
+        // we are the only writer
+        self.len
+            .store(self.len.load(Ordering::Relaxed) + 1, Ordering::Relaxed)
+    }
+    /// Make sure we can push `additional` elements without allocating.
+    pub(super) fn reserve(&self, additional: usize) {
+        if self.data.front().unwrap().remaining_space() < additional {
+            self.push_block(Block::with_capacity(additional));
         }
-        self.data.front_mut().unwrap().push(element)
+    }
+    /// Add given block in front of all others.
+    fn push_block(&self, block: Block<T>) {
+        self.capacity.fetch_add(block.capacity(), Ordering::Relaxed);
+        self.data.push_front(block);
+    }
+    /// Number of elements stored.
+    /// This is safe to call while the owning thread pushes.
+    pub(super) fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+    /// Number of elements we can store without allocating, including stored ones.
+    /// This is safe to call while the owning thread pushes.
+    pub(super) fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
     }
     pub(super) fn reset(&self) {
         self.data.reset();
-        let first_block = Block::new();
-        self.data.push_front(first_block);
+        self.len.store(0, Ordering::Relaxed);
+        self.capacity.store(0, Ordering::Relaxed);
+        self.push_block(Block::new());
     }
 }
 
@@ -90,3 +140,22 @@ impl<T: 'static> Storage<T> {
         blocks.into_iter().rev().flat_map(|b| b.iter())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn reserve_test() {
+        let storage = Storage::new();
+        storage.reserve(3 * BLOCK_SIZE);
+        (0..3 * BLOCK_SIZE).for_each(|i| storage.push(i));
+        // the initial empty block and the reserved one
+        assert_eq!(storage.data.iter().count(), 2);
+        assert!(storage.iter().cloned().eq(0..3 * BLOCK_SIZE));
+        assert_eq!(storage.len(), 3 * BLOCK_SIZE);
+        assert_eq!(storage.capacity(), 4 * BLOCK_SIZE);
+        storage.reset();
+        assert_eq!(storage.len(), 0);
+        assert_eq!(storage.capacity(), BLOCK_SIZE);
+    }
+}
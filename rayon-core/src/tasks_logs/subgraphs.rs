@@ -3,6 +3,42 @@
 use super::next_task_id;
 use super::now;
 use super::RawEvent;
+use super::{log, log_task_metric, logging_tasks};
+use super::{TaskId, EXTERNAL_TASK_FLAG};
+use super::{ThreadStorage, LOG_ROOT_SUBGRAPHS_ONLY, SUBGRAPHS_DEPTH, THREAD_LOGS};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+/// Units registered by this thread in a storage, kept weakly to notice when the thread
+/// switches to another storage.
+type RegisteredUnits = (Weak<ThreadStorage>, HashMap<&'static str, &'static str>);
+
+thread_local! {
+    /// Number of `sampled_subgraph` calls to skip before logging the next one on this thread.
+    static SKIPPED_CALLS: Cell<usize> = const { Cell::new(0) };
+    /// Units already registered by `custom_subgraph_with_unit` on this thread.
+    static REGISTERED_UNITS: RefCell<RegisteredUnits> = RefCell::new((Weak::new(), HashMap::new()));
+}
+
+/// Keeps track of the subgraphs depth of current thread while alive
+/// (even if the subgraph's op panics).
+struct DepthGuard;
+
+impl DepthGuard {
+    /// Enter a subgraph, returning the depth at which it is.
+    fn enter() -> (DepthGuard, usize) {
+        let depth = SUBGRAPHS_DEPTH.with(|d| d.replace(d.get() + 1));
+        (DepthGuard, depth)
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        SUBGRAPHS_DEPTH.with(|d| d.set(d.get() - 1))
+    }
+}
+
 /// We tag all the tasks that op makes as one subgraph.
 ///
 /// `work_type` is a str tag and `work_amount` an integer specifying the expected algorithmic cost
@@ -15,7 +51,7 @@ use super::RawEvent;
 /// Example:
 ///
 /// ```
-/// use rayon_logs::{join, subgraph};
+/// use rayon_core::{join, subgraph};
 ///
 /// fn manual_max(slice: &[u32]) -> u32 {
 ///     if slice.len() < 200_000 {
@@ -53,41 +89,210 @@ where
 /// The start function will be called just before running the graph and produce an S.
 /// The end function will be called just after running the graph on this S and produce a usize
 /// which will the be stored for display.
+///
+/// With the `log` feature enabled, a `log::trace!` message is also emitted at the end of
+/// each subgraph with its label, duration in nanoseconds and measured value.
 pub fn custom_subgraph<OP, R, START, END, S>(tag: &'static str, start: START, end: END, op: OP) -> R
 where
     OP: FnOnce() -> R,
     START: FnOnce() -> S,
     END: FnOnce(S) -> usize,
 {
+    let (_guard, depth) = DepthGuard::enter();
+    if depth > 0 && LOG_ROOT_SUBGRAPHS_ONLY.with(|l| l.get()) {
+        return op();
+    }
     let s = start();
+    #[cfg(feature = "log")]
+    let start_time = now();
     start_subgraph(tag);
     let r = op();
     let measured_value = end(s);
     end_subgraph(tag, measured_value);
+    #[cfg(feature = "log")]
+    ::log::trace!(
+        "subgraph {} ended after {}ns (work: {})",
+        tag,
+        now() - start_time,
+        measured_value
+    );
     r
 }
 
+/// Like `subgraph` but with a fractional work amount (normalized fractions,
+/// estimated flops,...).
+/// The work is stored without any rounding in a `RawEvent::SubgraphEndF64` event.
+pub fn custom_subgraph_f64<OP, R>(tag: &'static str, work: f64, op: OP) -> R
+where
+    OP: FnOnce() -> R,
+{
+    let (_guard, depth) = DepthGuard::enter();
+    if depth > 0 && LOG_ROOT_SUBGRAPHS_ONLY.with(|l| l.get()) {
+        return op();
+    }
+    start_subgraph(tag);
+    let r = op();
+    close_subgraph(RawEvent::SubgraphEndF64(tag, work.to_bits()));
+    r
+}
+
+/// Like `subgraph` but only log roughly one call out of `rate` (with a work amount multiplied
+/// by `rate`, saturating at `usize::MAX`) to bound logging overhead and logs size in hot loops.
+///
+/// Calls are counted per thread so the logged calls are not chosen randomly.
+/// Aggregated metrics (total work, average speed) stay approximately right as long as the
+/// sampled calls are representative of all calls but individual subgraphs are lost.
+/// A `rate` of 0 or 1 logs every call.
+pub fn sampled_subgraph<OP, R>(
+    work_type: &'static str,
+    work_amount: usize,
+    rate: usize,
+    op: OP,
+) -> R
+where
+    OP: FnOnce() -> R,
+{
+    let rate = rate.max(1);
+    let sampled = SKIPPED_CALLS.with(|calls_to_skip| {
+        let remaining = calls_to_skip.get();
+        if remaining == 0 {
+            calls_to_skip.set(rate - 1);
+            true
+        } else {
+            calls_to_skip.set(remaining - 1);
+            false
+        }
+    });
+    if sampled {
+        subgraph(work_type, work_amount.saturating_mul(rate), op)
+    } else {
+        op()
+    }
+}
+
+/// Like `custom_subgraph` but also specify the unit of the measured value (for example
+/// "items" or "bytes").
+/// The unit is stored once for each tag by each thread and will be available in
+/// `RawLogs::units` (unless threads registered different units for the same tag).
+pub fn custom_subgraph_with_unit<OP, R, START, END, S>(
+    tag: &'static str,
+    unit: &'static str,
+    start: START,
+    end: END,
+    op: OP,
+) -> R
+where
+    OP: FnOnce() -> R,
+    START: FnOnce() -> S,
+    END: FnOnce(S) -> usize,
+{
+    register_unit(tag, unit);
+    custom_subgraph(tag, start, end, op)
+}
+
+/// Store the unit of given tag in the storage of this thread,
+/// only locking it if the tag is new or its unit changed.
+fn register_unit(tag: &'static str, unit: &'static str) {
+    THREAD_LOGS.with(|l| {
+        let storage = l.borrow();
+        REGISTERED_UNITS.with(|r| {
+            let (registered_storage, units) = &mut *r.borrow_mut();
+            // holding the weak pointer prevents another storage from reusing the address
+            if registered_storage.as_ptr() != Arc::as_ptr(&storage) {
+                *registered_storage = Arc::downgrade(&storage);
+                units.clear();
+            }
+            if units.insert(tag, unit) != Some(unit) {
+                storage.units.lock().unwrap().insert(tag, unit);
+            }
+        })
+    })
+}
+
+/// Log `op` as a task with a caller provided id, tagged as a subgraph with given label and
+/// work amount (like `subgraph`).
+/// This allows to relate tasks of an external scheduler to the logged graph.
+///
+/// External ids live in their own namespace: they are logged with `EXTERNAL_TASK_FLAG` set
+/// and rayon's own ids never have this bit set.
+/// `my_id` must therefore fit in 63 bits (this is checked in debug builds).
+pub fn log_external_task<OP, R>(my_id: u64, label: &'static str, work_amount: usize, op: OP) -> R
+where
+    OP: FnOnce() -> R,
+{
+    debug_assert_eq!(
+        my_id & EXTERNAL_TASK_FLAG,
+        0,
+        "external task id {} does not fit in 63 bits",
+        my_id
+    );
+    let (_guard, depth) = DepthGuard::enter();
+    if depth > 0 && LOG_ROOT_SUBGRAPHS_ONLY.with(|l| l.get()) {
+        return op();
+    }
+    start_subgraph_task(my_id | EXTERNAL_TASK_FLAG, label);
+    let r = op();
+    end_subgraph(label, work_amount);
+    r
+}
+
+/// Record the priority a scheduler gave to the task running on this thread.
+/// Call it just after the task starts.
+/// Priority 0 is the default and is not recorded, see `RawLogs::task_priorities`.
+pub fn log_task_priority(priority: u8) {
+    if priority != 0 {
+        log(RawEvent::TaskPriority(priority))
+    }
+}
+
+/// Record a named value measured now on this thread (for example the length of a
+/// queue), to correlate it with the tasks, see `RawLogs::counters`.
+/// Counter names are stored alongside subgraph labels.
+pub fn tag_value(name: &'static str, value: u64) {
+    subgraph_logs!(RawEvent::Counter(name, value, now()))
+}
+
 /// Stop current task (virtually) and start a subgraph.
 /// You most likely don't need to call this function directly but `subgraph` instead.
 fn start_subgraph(tag: &'static str) {
-    let subgraph_start_task_id = next_task_id();
-    logs!(
-        // log child's work and dependencies.
-        RawEvent::Child(subgraph_start_task_id),
-        // end current task
-        RawEvent::TaskEnd(now()),
-        // execute full sequential task
-        RawEvent::TaskStart(subgraph_start_task_id, now()),
-        RawEvent::SubgraphStart(tag)
-    );
+    start_subgraph_task(next_task_id(), tag)
+}
+
+/// Stop current task (virtually) and start a subgraph in a task with given id.
+/// If the pool only logs subgraphs, the subgraph is still timed by a task around it.
+fn start_subgraph_task(subgraph_start_task_id: TaskId, tag: &'static str) {
+    if logging_tasks() {
+        log_task_metric();
+        subgraph_logs!(
+            // log child's work and dependencies.
+            RawEvent::Child(subgraph_start_task_id),
+            // end current task
+            RawEvent::TaskEnd(now()),
+            // execute full sequential task
+            RawEvent::TaskStart(subgraph_start_task_id, now())
+        );
+    } else {
+        subgraph_logs!(RawEvent::TaskStart(subgraph_start_task_id, now()));
+    }
+    subgraph_logs!(RawEvent::SubgraphStart(tag));
 }
 
 /// Stop current task (virtually) and end a subgraph.
 /// You most likely don't need to call this function directly but `subgraph` instead.
 fn end_subgraph(tag: &'static str, measured_value: usize) {
+    close_subgraph(RawEvent::SubgraphEnd(tag, measured_value))
+}
+
+/// Stop current task (virtually) and log given subgraph end event.
+fn close_subgraph(end_event: RawEvent<&'static str>) {
+    if !logging_tasks() {
+        subgraph_logs!(end_event, RawEvent::TaskEnd(now()));
+        return;
+    }
     let continuation_task_id = next_task_id();
-    logs!(
-        RawEvent::SubgraphEnd(tag, measured_value),
+    log_task_metric();
+    subgraph_logs!(
+        end_event,
         RawEvent::Child(continuation_task_id),
         RawEvent::TaskEnd(now()),
         // start continuation task
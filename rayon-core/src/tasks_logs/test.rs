@@ -0,0 +1,2395 @@
+//! Tests for the tasks logs.
+
+use crate::join;
+use crate::tasks_logs::{
+    log_external_task, EventKinds, Logger, PoolConfig, RawEvent, RawLogs, SubgraphMetric,
+    ThreadStatistics, EXTERNAL_TASK_FLAG,
+};
+
+#[test]
+fn subgraphs_only_logging() {
+    let logger = Logger::new();
+    let pool = logger
+        .pool_builder()
+        .num_threads(2)
+        .log_tasks(false)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        join(
+            || join(|| 1, || 2),
+            || {
+                crate::subgraph("leaf", 1, || {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                    join(|| 3, || 4)
+                })
+            },
+        )
+    });
+    let logs = logger.extract_logs();
+    // pool threads only recorded the subgraph, inside a task timing it
+    let workers_events: Vec<_> = logs.thread_events.iter().skip(1).flatten().collect();
+    assert_eq!(workers_events.len(), 4);
+    assert!(matches!(workers_events[0], RawEvent::TaskStart(..)));
+    assert!(matches!(workers_events[1], RawEvent::SubgraphStart(_)));
+    assert!(matches!(workers_events[2], RawEvent::SubgraphEnd(_, 1)));
+    assert!(matches!(workers_events[3], RawEvent::TaskEnd(_)));
+    assert!(logs.validate().is_empty());
+    let report = logs.subgraph_report();
+    assert!(report[0].inclusive_time >= 1_000_000);
+    assert_eq!(report[0].inclusive_time, report[0].exclusive_time);
+}
+
+#[test]
+fn unterminated_tasks() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(10),
+                RawEvent::TaskStart(2, 20),
+            ],
+            vec![RawEvent::TaskStart(1, 12), RawEvent::TaskEnd(15)],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(logs.unterminated_tasks(), vec![(0, 2)]);
+}
+
+#[test]
+fn subgraphs_units() {
+    let logger = Logger::new();
+    crate::custom_subgraph_with_unit("copy", "bytes", || (), |_| 1024, || ());
+    crate::subgraph("no unit", 1, || ());
+    let logs = logger.extract_logs();
+    assert_eq!(logs.labels, vec!["copy".to_string(), "no unit".to_string()]);
+    assert_eq!(logs.units, vec![Some("bytes".to_string()), None]);
+}
+
+#[test]
+fn units_of_each_logger() {
+    for unit in &["bytes", "items"] {
+        let logger = Logger::new();
+        for _ in 0..2 {
+            crate::custom_subgraph_with_unit("copy", unit, || (), |_| 1, || ());
+        }
+        // a new logger gets the units again
+        assert_eq!(logger.extract_logs().units, vec![Some(unit.to_string())]);
+    }
+    // changing the unit of a label is registered
+    let logger = Logger::new();
+    crate::custom_subgraph_with_unit("copy", "bytes", || (), |_| 1, || ());
+    crate::custom_subgraph_with_unit("copy", "items", || (), |_| 1, || ());
+    assert_eq!(logger.extract_logs().units, vec![Some("items".to_string())]);
+}
+
+#[test]
+fn conflicting_units() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(1).build().unwrap();
+    crate::custom_subgraph_with_unit("copy", "bytes", || (), |_| 1024, || ());
+    crate::custom_subgraph_with_unit("sort", "items", || (), |_| 10, || ());
+    pool.install(|| {
+        crate::custom_subgraph_with_unit("copy", "words", || (), |_| 128, || ());
+        crate::custom_subgraph_with_unit("sort", "items", || (), |_| 10, || ());
+    });
+    let logs = logger.extract_logs();
+    assert_eq!(logs.labels, vec!["copy".to_string(), "sort".to_string()]);
+    assert_eq!(logs.units, vec![None, Some("items".to_string())]);
+}
+
+#[test]
+fn lenient_loading() {
+    let mut logger = Logger::new();
+    crate::subgraph("work", 3, || ());
+    let path = std::env::temp_dir().join(format!("rayon_lenient_{}.rlog", std::process::id()));
+    logger.save_raw_logs(&path).unwrap();
+    let (complete, truncated) = RawLogs::load_lenient(&path).unwrap();
+    assert!(!truncated);
+    // main task start and end around the 8 events of the subgraph
+    assert_eq!(complete.thread_events[0].len(), 10);
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+    let (partial, truncated) = RawLogs::load_lenient(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(truncated);
+    assert_eq!(partial.labels, complete.labels);
+    assert_eq!(partial.thread_events[0][..], complete.thread_events[0][..9]);
+}
+
+#[test]
+fn malformed_logs() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart(0),
+            RawEvent::SubgraphEnd(0, 2),
+            RawEvent::BlockStart(3),
+            RawEvent::BlockEnd(4),
+            RawEvent::TaskEnd(10),
+        ]],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let mut valid = Vec::new();
+    logs.write_to(&mut valid).unwrap();
+    // an unknown label is rejected
+    let mut unknown_label = valid.clone();
+    let label_index = valid.len()
+        - logs.thread_events[0][2..]
+            .iter()
+            .map(|event| event.encoded_len())
+            .sum::<usize>()
+        - 8;
+    assert_eq!(
+        unknown_label[label_index - 1],
+        crate::wire_format::TAG_SUBGRAPH_START
+    );
+    unknown_label[label_index] = 1;
+    let error = RawLogs::read_lenient_from(&mut &unknown_label[..]).unwrap_err();
+    assert_eq!(error.io_error().kind(), std::io::ErrorKind::InvalidData);
+    // failures are located
+    let mut unknown_tag = valid.clone();
+    unknown_tag[label_index - 1] = 127;
+    match RawLogs::read_lenient_from(&mut &unknown_tag[..]) {
+        Err(crate::tasks_logs::LogError::ReadingEvent { thread, index, .. }) => {
+            assert_eq!((thread, index), (0, 1))
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    // random corruptions never panic (see also the fuzz target)
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..10_000 {
+        let mut bytes = valid.clone();
+        for _ in 0..1 + random() % 4 {
+            let index = random() as usize % bytes.len();
+            bytes[index] = random() as u8;
+        }
+        bytes.truncate(random() as usize % (bytes.len() + 1));
+        if let Ok((logs, _)) = RawLogs::read_lenient_from(&mut &bytes[..]) {
+            analyze(&logs);
+        }
+    }
+    // sums of huge durations saturate
+    let huge = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::PhaseStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::BlockStart(1),
+                RawEvent::BlockEnd(u64::MAX - 1),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(u64::MAX),
+                RawEvent::TaskStart(1, u64::MAX),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(u64::MAX),
+            ],
+            vec![
+                RawEvent::TaskStart(2, 0),
+                RawEvent::Child(0),
+                RawEvent::TaskEnd(u64::MAX),
+            ],
+        ],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    huge.write_to(&mut bytes).unwrap();
+    let huge = RawLogs::read_from(&mut &bytes[..]).unwrap();
+    analyze(&huge);
+    assert_eq!(huge.estimated_sequential_time(), u64::MAX);
+    assert_eq!(huge.statistics().threads[0].busy_time, u64::MAX);
+    assert!(huge.ci_line().contains("work_ms=18446744073709"));
+}
+
+/// Run all analyses on given logs (which must not panic).
+fn analyze(logs: &RawLogs) {
+    logs.validate();
+    logs.subgraph_report();
+    logs.statistics().parallel_efficiency();
+    logs.grain_report(1_000);
+    logs.ci_line();
+    logs.task_tree().depth_first().for_each(drop);
+    logs.fork_depth_histogram();
+    logs.queue_depth_timeline(16);
+    logs.occupancy_by_label(16);
+    logs.idle_gaps();
+    logs.phases();
+    logs.blocked_times();
+    logs.merged_tasks();
+    logs.downsample(4);
+    let mut coalesced = logs.filter_kinds(EventKinds::ALL);
+    coalesced.coalesce_subgraphs(1_000);
+    coalesced.validate();
+    logs.to_spans_json(&mut std::io::sink()).unwrap();
+    logs.write_chrome_trace(&mut std::io::sink()).unwrap();
+    logs.dump(&mut std::io::sink()).unwrap();
+}
+
+#[test]
+fn queue_depth() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(1),
+                RawEvent::Child(2),
+                RawEvent::TaskEnd(10),
+                RawEvent::TaskStart(2, 40),
+                RawEvent::TaskEnd(50),
+            ],
+            vec![RawEvent::TaskStart(1, 20), RawEvent::TaskEnd(30)],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(
+        logs.queue_depth_timeline(6),
+        vec![(0, 0), (10, 2), (20, 1), (30, 1), (40, 0), (50, 0)]
+    );
+    // long logs do not overflow
+    let long = RawLogs::from_parts(
+        vec![vec![RawEvent::TaskStart(0, 0), RawEvent::TaskEnd(u64::MAX)]],
+        Vec::new(),
+    )
+    .unwrap();
+    let times: Vec<_> = long
+        .queue_depth_timeline(3)
+        .into_iter()
+        .map(|(time, _)| time)
+        .collect();
+    assert_eq!(times, vec![0, u64::MAX / 2, u64::MAX]);
+    // logs without tasks are sampled over their other timed events
+    let markers = RawLogs::from_parts(
+        vec![vec![RawEvent::Marker(0, 10), RawEvent::Counter(0, 3, 30)]],
+        vec!["mark".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        markers.queue_depth_timeline(3),
+        vec![(10, 0), (20, 0), (30, 0)]
+    );
+}
+
+#[test]
+fn labels_colors() {
+    let logs =
+        RawLogs::from_parts(Vec::new(), vec!["sort".to_string(), "merge".to_string()]).unwrap();
+    let other_logs = RawLogs::from_parts(Vec::new(), vec!["merge".to_string()]).unwrap();
+    assert_eq!(logs.label_color(1), other_logs.label_color(0));
+    let palette = [(0, 0, 0)];
+    assert_eq!(logs.label_color_in(0, &palette), (0, 0, 0));
+}
+
+#[test]
+fn subgraphs_and_logger_share_state() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    crate::subgraph("outside", 1, || ());
+    pool.install(|| crate::subgraph("inside", 2, || ()));
+    let logs = logger.extract_logs();
+    let mut labels = logs.labels.clone();
+    labels.sort();
+    assert_eq!(labels, vec!["inside".to_string(), "outside".to_string()]);
+}
+
+#[test]
+fn labels_occupancy() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(5),
+                RawEvent::TaskStart(1, 5),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEnd(0, 3),
+                RawEvent::Child(2),
+                RawEvent::TaskEnd(20),
+                RawEvent::TaskStart(2, 20),
+                RawEvent::TaskEnd(30),
+            ],
+            vec![
+                RawEvent::TaskStart(3, 0),
+                RawEvent::Child(4),
+                RawEvent::TaskEnd(10),
+                RawEvent::TaskStart(4, 10),
+                RawEvent::SubgraphStart(1),
+                RawEvent::SubgraphEnd(1, 1),
+                RawEvent::Child(5),
+                RawEvent::TaskEnd(25),
+                RawEvent::TaskStart(5, 25),
+                RawEvent::TaskEnd(30),
+            ],
+        ],
+        vec!["a".to_string(), "b".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        logs.occupancy_by_label(4),
+        vec![
+            (0, vec![]),
+            (10, vec![(0, 1), (1, 1)]),
+            (20, vec![(1, 1)]),
+            (30, vec![])
+        ]
+    );
+}
+
+#[test]
+fn logged_pool_config() {
+    let logger = Logger::new();
+    assert_eq!(logger.extract_logs().pool_config(), None);
+    let _pool = logger
+        .pool_builder()
+        .num_threads(3)
+        .stack_size(1 << 20)
+        .build()
+        .unwrap();
+    assert_eq!(
+        logger.extract_logs().pool_config(),
+        Some(&PoolConfig {
+            num_threads: 3,
+            stack_size: Some(1 << 20),
+            breadth_first: false,
+        })
+    );
+}
+
+#[test]
+fn sampled_subgraphs() {
+    let logger = Logger::new();
+    (0..10).for_each(|_| crate::sampled_subgraph("hot", 2, 5, || ()));
+    // scaled work saturates instead of overflowing
+    crate::sampled_subgraph("huge", usize::MAX / 2, 3, || ());
+    let logs = logger.extract_logs();
+    let works: Vec<usize> = logs.thread_events[0]
+        .iter()
+        .filter_map(|e| match e {
+            RawEvent::SubgraphEnd(_, work) => Some(*work),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(works, vec![10, 10, usize::MAX]);
+}
+
+#[test]
+fn logs_from_parts() {
+    assert!(RawLogs::from_parts(vec![vec![RawEvent::SubgraphStart(0)]], Vec::new()).is_err());
+    let logs = RawLogs::from_parts(
+        vec![vec![RawEvent::SubgraphStart(0)]],
+        vec!["label".to_string()],
+    )
+    .unwrap();
+    assert_eq!(logs.units, vec![None]);
+}
+
+#[test]
+fn tasks_durations() {
+    let events: Vec<RawEvent<usize>> = vec![
+        RawEvent::TaskStart(0, 0),
+        RawEvent::TaskStart(1, 2),
+        RawEvent::TaskEnd(5),
+        RawEvent::Child(2),
+        RawEvent::TaskEnd(10),
+        RawEvent::TaskStart(2, 12),
+    ];
+    assert_eq!(
+        RawEvent::task_durations(&events).collect::<Vec<_>>(),
+        vec![(1, 3), (0, 10)]
+    );
+}
+
+#[test]
+fn label_timeline() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::TaskEnd(5),
+                RawEvent::TaskStart(1, 8),
+                RawEvent::SubgraphStart(1),
+                RawEvent::SubgraphEnd(1, 1),
+                RawEvent::TaskEnd(12),
+                RawEvent::SubgraphEnd(0, 2),
+                RawEvent::TaskEnd(20),
+            ],
+            vec![
+                RawEvent::TaskStart(2, 1),
+                RawEvent::SubgraphStart(1),
+                RawEvent::SubgraphEnd(1, 1),
+                RawEvent::TaskEnd(3),
+            ],
+        ],
+        vec!["outer".to_string(), "inner".to_string()],
+    )
+    .unwrap();
+    assert_eq!(logs.label_timeline("outer"), vec![(0, 0, 20)]);
+    assert_eq!(logs.label_timeline("inner"), vec![(1, 1, 3), (0, 8, 12)]);
+    assert!(logs.label_timeline("missing").is_empty());
+}
+
+#[test]
+fn flush_one_thread() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(1).build().unwrap();
+    pool.install(|| crate::subgraph("worker", 1, || ()));
+    crate::subgraph("main", 1, || ());
+    assert!(logger.flush_thread(Some(1)).is_none());
+    let worker_index = pool.install(crate::current_thread_index);
+    let worker_logs = logger.flush_thread(worker_index).unwrap();
+    assert_eq!(worker_logs.thread_events.len(), 1);
+    assert_eq!(worker_logs.labels, vec!["worker".to_string()]);
+    assert!(logger.flush_thread(Some(0)).unwrap().thread_events[0].is_empty());
+    assert_eq!(logger.extract_logs().labels, vec!["main".to_string()]);
+}
+
+/// Call `join` on empty closures, returning the call site it logs.
+#[cfg(feature = "join_labels")]
+#[track_caller]
+fn located_join() -> &'static std::panic::Location<'static> {
+    join(|| (), || ());
+    std::panic::Location::caller()
+}
+
+#[cfg(feature = "join_labels")]
+#[test]
+fn joins_logging() {
+    let logger = Logger::new();
+    let pool = logger
+        .pool_builder()
+        .num_threads(2)
+        .log_joins(true)
+        .build()
+        .unwrap();
+    // later calls from the same site use cached labels
+    let locations: Vec<_> = pool.install(|| (0..10).map(|_| located_join()).collect());
+    let location = locations[0];
+    let logs = logger.extract_logs();
+    let mut labels = logs.labels.clone();
+    labels.sort();
+    assert_eq!(
+        labels,
+        vec![
+            format!("join a at {}", location),
+            format!("join b at {}", location)
+        ]
+    );
+}
+
+#[cfg(not(feature = "join_labels"))]
+#[test]
+fn joins_logging() {
+    let logger = Logger::new();
+    let pool = logger
+        .pool_builder()
+        .num_threads(2)
+        .log_joins(true)
+        .build()
+        .unwrap();
+    pool.install(|| join(|| (), || ()));
+    let logs = logger.extract_logs();
+    // without call sites, joins are not logged as subgraphs
+    assert!(logs.labels.is_empty());
+    assert!(logs.event_count() > 0);
+}
+
+#[test]
+fn labels_lookups() {
+    let mut logs = RawLogs::from_parts(Vec::new(), vec!["a".to_string(), "b".to_string()]).unwrap();
+    assert_eq!(logs.label_id("b"), Some(1));
+    assert_eq!(logs.label_id("c"), None);
+    assert_eq!(logs.label(0), Some("a"));
+    assert_eq!(logs.label(2), None);
+    // lookups follow changes to the labels
+    logs.labels.push("c".to_string());
+    assert_eq!(logs.label_id("c"), Some(2));
+    // repeated labels keep their first id
+    logs.labels.push("a".to_string());
+    let ids = logs.labels_ids();
+    assert_eq!(ids.len(), 3);
+    assert_eq!((ids["a"], ids["b"], ids["c"]), (0, 1, 2));
+}
+
+#[test]
+fn task_thread() {
+    let mut logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(5),
+            ],
+            vec![RawEvent::TaskStart(1, 6), RawEvent::TaskEnd(9)],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(logs.task_thread(0), Some(0));
+    assert_eq!(logs.task_thread(1), Some(1));
+    assert_eq!(logs.task_thread(2), None);
+    // lookups follow changes to the events
+    logs.thread_events[1].push(RawEvent::TaskStart(2, 10));
+    assert_eq!(logs.task_thread(2), Some(1));
+    let threads = logs.tasks_threads();
+    assert_eq!(threads.len(), 3);
+    assert_eq!(threads[&1], 1);
+}
+
+#[test]
+fn external_tasks() {
+    let logger = Logger::new();
+    log_external_task(42, "external", 7, || ());
+    let logs = logger.extract_logs();
+    let external_starts: Vec<_> = logs.thread_events[0]
+        .iter()
+        .filter_map(|e| match e {
+            RawEvent::TaskStart(id, _) if id & EXTERNAL_TASK_FLAG != 0 => Some(*id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(external_starts, vec![42 | EXTERNAL_TASK_FLAG]);
+    assert_eq!(logs.labels, vec!["external".to_string()]);
+    assert_eq!(logs.subgraph_report()[0].work, 7.0);
+    // nested external tasks follow `log_root_subgraphs_only`
+    let logger = Logger::new();
+    let pool = logger
+        .pool_builder()
+        .num_threads(1)
+        .log_root_subgraphs_only(true)
+        .build()
+        .unwrap();
+    pool.install(|| crate::subgraph("outer", 1, || log_external_task(1, "external", 1, || ())));
+    assert_eq!(logger.extract_logs().labels, vec!["outer".to_string()]);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "does not fit in 63 bits")]
+fn external_tasks_ids_range() {
+    log_external_task(EXTERNAL_TASK_FLAG | 1, "external", 1, || ());
+}
+
+#[test]
+fn grain_report() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::TaskEnd(500),
+                RawEvent::TaskStart(1, 600),
+                RawEvent::TaskEnd(3_000),
+            ],
+            vec![RawEvent::TaskStart(2, 100), RawEvent::TaskEnd(200)],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    let report = logs.grain_report(1_000);
+    assert_eq!(report.tasks, vec![2, 1]);
+    assert_eq!(report.small_tasks, vec![1, 1]);
+    assert!((report.small_fraction() - 2.0 / 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn subgraph_report() {
+    // "outer" runs from 0 to 20 and contains "inner" from 5 to 15
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(5),
+            RawEvent::TaskStart(1, 5),
+            RawEvent::SubgraphStart(1),
+            RawEvent::SubgraphEnd(1, 3),
+            RawEvent::TaskEnd(15),
+            RawEvent::TaskStart(2, 15),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::TaskEnd(20),
+        ]],
+        vec!["outer".to_string(), "inner".to_string()],
+    )
+    .unwrap();
+    let report = logs.subgraph_report();
+    assert_eq!(report[0].count, 1);
+    assert_eq!(report[0].inclusive_time, 20);
+    assert_eq!(report[0].exclusive_time, 10);
+    assert_eq!(report[1].work, 3.0);
+    assert_eq!(report[0].work, 1.0);
+    assert_eq!(report[0].inclusive_work, 4.0);
+    assert_eq!(report[1].inclusive_work, 3.0);
+    assert_eq!(report[1].inclusive_time, 10);
+    assert_eq!(report[1].exclusive_time, 10);
+    assert_eq!(report[0].peak_concurrency, 1);
+    let by_work = logs.subgraph_report_sorted_by(SubgraphMetric::Work);
+    assert_eq!(by_work[0].label, 1);
+    // equal exclusive times keep labels order
+    let by_self_time = logs.subgraph_report_sorted_by(SubgraphMetric::ExclusiveTime);
+    assert_eq!(by_self_time[0].label, 0);
+    let by_total_time = logs.subgraph_report_sorted_by(SubgraphMetric::InclusiveTime);
+    assert_eq!(by_total_time[0].label, 0);
+    let by_total_work = logs.subgraph_report_sorted_by(SubgraphMetric::InclusiveWork);
+    assert_eq!(by_total_work[0].label, 0);
+}
+
+#[test]
+fn subgraphs_peak_concurrency() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(10),
+            ],
+            vec![
+                RawEvent::TaskStart(1, 5),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::SubgraphStart(1),
+                RawEvent::SubgraphEnd(1, 1),
+                RawEvent::TaskEnd(20),
+            ],
+        ],
+        vec!["shared".to_string(), "serial".to_string()],
+    )
+    .unwrap();
+    let report = logs.subgraph_report();
+    // recursion on thread 0 does not count twice
+    assert_eq!(report[0].peak_concurrency, 2);
+    assert_eq!(report[1].peak_concurrency, 1);
+}
+
+#[test]
+fn validate() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(10),
+            ],
+            vec![RawEvent::TaskEnd(3)],
+            vec![RawEvent::TaskStart(1, 4), RawEvent::SubgraphStart(0)],
+        ],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        logs.validate(),
+        vec![
+            "thread 1: task end without task at event 0".to_string(),
+            "thread 2: task 1 never ends".to_string(),
+            "thread 2: subgraph 0 never ends".to_string(),
+        ]
+    );
+    // validating inside a logged pool does not log the validation's tasks
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    let (before, problems, after) = pool.install(|| {
+        let before = logger.events_count();
+        let problems = logs.validate();
+        (before, problems, logger.events_count())
+    });
+    assert_eq!(after, before);
+    assert_eq!(problems, logs.validate());
+    assert_eq!(problems.len(), 3);
+    // and tasks are logged again once done
+    pool.install(|| join(|| (), || ()));
+    assert!(logger.events_count() > after);
+}
+
+#[test]
+fn estimated_sequential_time() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                // nested task is already counted in its parent
+                RawEvent::TaskStart(1, 2),
+                RawEvent::TaskEnd(4),
+                RawEvent::TaskEnd(10),
+                // never ends
+                RawEvent::TaskStart(2, 20),
+            ],
+            vec![RawEvent::TaskStart(3, 5), RawEvent::TaskEnd(12)],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(logs.estimated_sequential_time(), 17);
+}
+
+#[test]
+fn spans_json() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(5),
+            ],
+            vec![
+                RawEvent::TaskStart(1, 6),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphStart(1),
+                RawEvent::SubgraphEnd(1, 1),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(9),
+            ],
+        ],
+        vec!["outer".to_string(), "\"inner\"".to_string()],
+    )
+    .unwrap();
+    let mut json = Vec::new();
+    logs.to_spans_json(&mut json).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        "[{\"thread\":0,\"start\":0,\"end\":5,\"label\":null,\"depth\":0},\
+         {\"thread\":1,\"start\":6,\"end\":9,\"label\":\"\\\"inner\\\"\",\"depth\":1}]"
+    );
+    // a stolen task is in the subgraph of its creator
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(5),
+                RawEvent::TaskStart(2, 8),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(9),
+            ],
+            vec![RawEvent::TaskStart(1, 6), RawEvent::TaskEnd(7)],
+        ],
+        vec!["outer".to_string()],
+    )
+    .unwrap();
+    let mut json = Vec::new();
+    logs.to_spans_json(&mut json).unwrap();
+    assert!(String::from_utf8(json)
+        .unwrap()
+        .contains("{\"thread\":1,\"start\":6,\"end\":7,\"label\":\"outer\",\"depth\":0}"));
+}
+
+#[test]
+fn single_event_bytes() {
+    let events = vec![
+        RawEvent::TaskStart(3, 10),
+        RawEvent::TaskEnd(12),
+        RawEvent::Child(4),
+        RawEvent::TaskMetric(7),
+        RawEvent::SubgraphStart(0),
+        RawEvent::SubgraphEnd(0, 5),
+        RawEvent::SubgraphEndF64(0, 0.5f64.to_bits()),
+        RawEvent::BlockStart(13),
+        RawEvent::BlockEnd(14),
+        RawEvent::ParallelDepth(2),
+        RawEvent::Marker(1, 15),
+        RawEvent::MergedTasks(12),
+        RawEvent::TaskPriority(3),
+        RawEvent::PhaseStart(0, 16),
+        RawEvent::Counter(1, 42, 17),
+    ];
+    for event in events {
+        let bytes = event.to_bytes();
+        assert_eq!(bytes.len(), event.encoded_len());
+        assert_eq!(RawEvent::from_bytes(&bytes).unwrap(), event);
+        let error = RawEvent::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        let mut padded = bytes.clone();
+        padded.push(0);
+        let error = RawEvent::from_bytes(&padded).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn logs_to_writer() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart(0),
+            RawEvent::SubgraphEnd(0, 2),
+            RawEvent::TaskEnd(10),
+        ]],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let mut buffer = Vec::new();
+    logs.write_to(&mut buffer).unwrap();
+    let (loaded, truncated) = RawLogs::read_lenient_from(&mut &buffer[..]).unwrap();
+    assert!(!truncated);
+    assert_eq!(loaded, logs);
+}
+
+#[test]
+fn partitioned_logs() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(5),
+            ],
+            vec![
+                RawEvent::TaskStart(1, 6),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEnd(0, 3),
+                RawEvent::TaskEnd(9),
+            ],
+        ],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let dir = std::env::temp_dir().join(format!("rayon_partitioned_{}", std::process::id()));
+    logs.save_partitioned(&dir).unwrap();
+    assert!(dir.join("thread_1.rlog").exists());
+    let loaded = RawLogs::load_partitioned(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(loaded, logs);
+}
+
+#[test]
+fn live_subgraph_count() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        join(
+            || crate::subgraph("phase", 1, || ()),
+            || crate::subgraph("phase", 1, || ()),
+        )
+    });
+    crate::subgraph("other", 1, || ());
+    assert_eq!(logger.subgraph_count("phase"), 2);
+    assert_eq!(logger.subgraph_count("other"), 1);
+    assert_eq!(logger.subgraph_count("missing"), 0);
+}
+
+#[test]
+fn live_label_activity() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    let activity = pool.install(|| {
+        crate::subgraph("outer", 1, || {
+            crate::subgraph("inner", 1, || logger.live_label_activity())
+        })
+    });
+    assert_eq!(activity.len(), 2);
+    assert_eq!(activity["outer"], 1);
+    assert_eq!(activity["inner"], 1);
+    assert!(logger.live_label_activity().is_empty());
+}
+
+#[test]
+fn overhead_estimate() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    // empty subgraphs: logging is most of the work
+    pool.install(|| (0..100).for_each(|_| crate::subgraph("tiny", 1, || ())));
+    let tiny = logger.overhead_estimate();
+    assert!(tiny > 0.0 && tiny <= 100.0);
+    assert_eq!(Logger::new().overhead_estimate(), 0.0);
+}
+
+#[test]
+fn live_work_and_span() {
+    let threads: Vec<Vec<RawEvent<usize>>> = vec![
+        vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::Child(1),
+            RawEvent::Child(2),
+            RawEvent::TaskEnd(10),
+            RawEvent::TaskStart(2, 10),
+            RawEvent::Child(3),
+            RawEvent::TaskEnd(15),
+            // not terminated
+            RawEvent::TaskStart(3, 30),
+        ],
+        vec![
+            RawEvent::TaskStart(1, 10),
+            RawEvent::Child(3),
+            RawEvent::TaskEnd(30),
+        ],
+    ];
+    assert_eq!(
+        crate::tasks_logs::analysis::span_of(threads.iter().map(|t| t.iter())),
+        30
+    );
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| join(|| (), || ()));
+    assert!(logger.current_span() <= logger.total_work());
+    assert!(logger.total_work() > 0);
+}
+
+#[test]
+fn resolved_events() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart(1),
+            RawEvent::SubgraphEnd(1, 4),
+            RawEvent::TaskEnd(3),
+        ]],
+        vec!["a".to_string(), "b".to_string()],
+    )
+    .unwrap();
+    let resolved: Vec<Vec<_>> = logs.resolved_events().map(|t| t.collect()).collect();
+    assert_eq!(
+        resolved,
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart("b"),
+            RawEvent::SubgraphEnd("b", 4),
+            RawEvent::TaskEnd(3),
+        ]]
+    );
+}
+
+#[test]
+fn tasks_ids_past_32_bits() {
+    use crate::tasks_logs::{next_task_id, THREAD_LOGS};
+    use std::sync::atomic::Ordering;
+    let _logger = Logger::new();
+    // ids are only shared with other threads of our logger
+    THREAD_LOGS.with(|l| {
+        l.borrow()
+            .task_ids
+            .store(u64::from(u32::MAX) - 1, Ordering::SeqCst)
+    });
+    let ids: Vec<_> = (0..4).map(|_| next_task_id()).collect();
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    assert!(ids[3] > u64::from(u32::MAX));
+    let logs = RawLogs::from_parts(
+        vec![vec![RawEvent::TaskStart(ids[3], 0), RawEvent::TaskEnd(1)]],
+        Vec::new(),
+    )
+    .unwrap();
+    let mut buffer = Vec::new();
+    logs.write_to(&mut buffer).unwrap();
+    let (loaded, _) = RawLogs::read_lenient_from(&mut &buffer[..]).unwrap();
+    assert_eq!(loaded.thread_events[0][0], RawEvent::TaskStart(ids[3], 0));
+}
+
+#[test]
+fn isolated_loggers() {
+    let first = Logger::new();
+    crate::subgraph("first", 1, || ());
+    let second = Logger::new();
+    crate::subgraph("second", 1, || ());
+    // extracting the older logger records nothing in the newer one
+    let first_logs = first.extract_logs();
+    let second_logs = second.extract_logs();
+    assert_eq!(first_logs.labels, vec!["first".to_string()]);
+    assert_eq!(second_logs.labels, vec!["second".to_string()]);
+    assert!(first_logs.validate().is_empty());
+    assert!(second_logs.validate().is_empty());
+}
+
+#[test]
+fn isolated_pools() {
+    let first = Logger::new();
+    crate::subgraph("first", 1, || ());
+    let second = Logger::new();
+    crate::subgraph("second", 1, || ());
+    let pool = first.pool_builder().num_threads(1).build().unwrap();
+    pool.install(|| crate::subgraph("first pool", 1, || ()));
+    let mut first_labels = first.extract_logs().labels;
+    first_labels.sort();
+    assert_eq!(
+        first_labels,
+        vec!["first".to_string(), "first pool".to_string()]
+    );
+    assert_eq!(second.extract_logs().labels, vec!["second".to_string()]);
+}
+
+#[test]
+fn text_dump() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 100),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(105),
+            RawEvent::TaskStart(1, 107),
+            RawEvent::SubgraphEnd(0, 3),
+            RawEvent::TaskEnd(110),
+        ]],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let mut out = Vec::new();
+    logs.dump(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "thread 0:
+  task 0 starts at 0ns
+  subgraph work starts
+    child 1
+    task ends at 5ns
+    task 1 starts at 7ns
+  subgraph work ends (work: 3)
+  task ends at 10ns
+"
+    );
+}
+
+#[test]
+fn root_subgraphs_only() {
+    let logger = Logger::new();
+    let pool = logger
+        .pool_builder()
+        .num_threads(1)
+        .log_root_subgraphs_only(true)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        crate::subgraph("outer", 1, || crate::subgraph("inner", 1, || ()));
+        crate::subgraph("second outer", 1, || ());
+    });
+    let mut labels = logger.extract_logs().labels;
+    labels.sort();
+    assert_eq!(
+        labels,
+        vec!["outer".to_string(), "second outer".to_string()]
+    );
+}
+
+#[test]
+fn root_subgraphs_only_in_stolen_tasks() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    let logger = Logger::new();
+    let pool = logger
+        .pool_builder()
+        .num_threads(2)
+        .log_root_subgraphs_only(true)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        crate::subgraph("outer", 1, || {
+            let stolen = AtomicBool::new(false);
+            // the first branch waits until the other thread steals the second one
+            join(
+                || {
+                    while !stolen.load(Ordering::SeqCst) {
+                        std::hint::spin_loop()
+                    }
+                },
+                || {
+                    crate::subgraph("inner", 1, || ());
+                    stolen.store(true, Ordering::SeqCst)
+                },
+            )
+        })
+    });
+    assert_eq!(logger.extract_logs().labels, vec!["outer".to_string()]);
+}
+
+#[test]
+fn occupancy_of_stolen_tasks() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(0),
+                RawEvent::TaskStart(1, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::Child(2),
+                RawEvent::Child(3),
+                RawEvent::TaskEnd(5),
+                RawEvent::TaskStart(2, 5),
+                RawEvent::Child(4),
+                RawEvent::TaskEnd(20),
+                RawEvent::TaskStart(4, 20),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(30),
+            ],
+            vec![
+                RawEvent::TaskStart(5, 0),
+                RawEvent::Child(6),
+                RawEvent::TaskEnd(1),
+                RawEvent::TaskStart(6, 1),
+                RawEvent::SubgraphStart(1),
+                RawEvent::Child(7),
+                RawEvent::TaskEnd(2),
+                // stealing task 3 while waiting inside subgraph 1
+                RawEvent::TaskStart(3, 6),
+                RawEvent::Child(4),
+                RawEvent::TaskEnd(18),
+                RawEvent::TaskStart(7, 25),
+                RawEvent::SubgraphEnd(1, 1),
+                RawEvent::TaskEnd(30),
+            ],
+        ],
+        vec!["a".to_string(), "b".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        logs.occupancy_by_label(4),
+        vec![
+            (0, vec![(0, 1)]),
+            (10, vec![(0, 2)]),
+            (20, vec![(0, 1)]),
+            (30, vec![])
+        ]
+    );
+}
+
+#[test]
+fn task_subgraph() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(1),
+            RawEvent::TaskStart(1, 1),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(2),
+            RawEvent::TaskEnd(2),
+            RawEvent::TaskStart(2, 2),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::Child(3),
+            RawEvent::TaskEnd(3),
+            RawEvent::TaskStart(3, 3),
+            RawEvent::TaskEnd(4),
+        ]],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let subgraphs = logs.task_subgraph();
+    assert_eq!(subgraphs[&0], None);
+    assert_eq!(subgraphs[&1], Some(0));
+    assert_eq!(subgraphs[&2], Some(0));
+    assert_eq!(subgraphs[&3], None);
+}
+
+#[test]
+fn stolen_task_subgraph() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::Child(1),
+                RawEvent::Child(2),
+                RawEvent::TaskEnd(1),
+                RawEvent::TaskStart(1, 1),
+                RawEvent::Child(3),
+                RawEvent::TaskEnd(5),
+                RawEvent::TaskStart(3, 6),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(7),
+            ],
+            vec![
+                RawEvent::TaskStart(4, 0),
+                RawEvent::SubgraphStart(1),
+                RawEvent::Child(5),
+                RawEvent::TaskEnd(1),
+                // stolen while waiting inside subgraph 1
+                RawEvent::TaskStart(2, 2),
+                RawEvent::Child(3),
+                RawEvent::TaskEnd(4),
+                RawEvent::TaskStart(5, 4),
+                RawEvent::SubgraphEnd(1, 1),
+                RawEvent::TaskEnd(5),
+            ],
+        ],
+        vec!["a".to_string(), "b".to_string()],
+    )
+    .unwrap();
+    let subgraphs = logs.task_subgraph();
+    assert_eq!(subgraphs[&2], Some(0));
+    assert_eq!(subgraphs[&3], Some(0));
+    assert_eq!(subgraphs[&5], Some(1));
+}
+
+#[test]
+fn compact_format() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 1_000_000),
+                RawEvent::SubgraphStart(0),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(1_000_100),
+                // times going backwards are still encoded
+                RawEvent::TaskStart(1, 999_000),
+                RawEvent::SubgraphEnd(0, 3),
+                RawEvent::TaskEnd(u64::MAX),
+            ],
+            Vec::new(),
+            vec![RawEvent::TaskStart(u64::MAX, 0)],
+        ],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let mut fixed = Vec::new();
+    logs.write_to(&mut fixed).unwrap();
+    let mut compact = Vec::new();
+    logs.write_compact_to(&mut compact).unwrap();
+    assert!(compact.len() < fixed.len());
+    let (loaded, truncated) = RawLogs::read_lenient_from(&mut &compact[..]).unwrap();
+    assert!(!truncated);
+    assert_eq!(loaded, logs);
+    // files from before the header (and provenance block) are still readable
+    let headerless = &fixed[super::wire_format::HEADER_LEN + 16..];
+    let (loaded, truncated) = RawLogs::read_lenient_from(&mut &headerless[..]).unwrap();
+    assert!(!truncated);
+    assert_eq!(loaded, logs);
+}
+
+#[test]
+fn byte_order() {
+    use crate::wire_format::{BYTE_ORDER_MARK, MAGIC};
+    let logs = RawLogs::from_parts(
+        vec![vec![RawEvent::TaskStart(1, 2), RawEvent::TaskEnd(3)]],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    logs.write_to(&mut bytes).unwrap();
+    // integers are little endian whatever the host
+    let header = MAGIC.len() + 1;
+    assert_eq!(bytes[header..header + 8], [8, 7, 6, 5, 4, 3, 2, 1]);
+    // labels number, after the empty provenance block
+    assert_eq!(bytes[header + 24..header + 32], [1, 0, 0, 0, 0, 0, 0, 0]);
+    // headers without the mark are rejected
+    let mut unmarked = bytes[..header].to_vec();
+    unmarked.extend_from_slice(&bytes[header + 8..]);
+    let error = RawLogs::read_lenient_from(&mut &unmarked[..]).unwrap_err();
+    assert_eq!(error.io_error().kind(), std::io::ErrorKind::InvalidData);
+    // a byte swapped file is rejected
+    bytes[header..header + 8].copy_from_slice(&BYTE_ORDER_MARK.to_be_bytes());
+    let error = RawLogs::read_lenient_from(&mut &bytes[..]).unwrap_err();
+    assert_eq!(error.io_error().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn task_metrics() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    let logger = Logger::new();
+    let measures = AtomicU64::new(0);
+    let pool = logger
+        .pool_builder()
+        .num_threads(1)
+        .task_metric(move || measures.fetch_add(1, Ordering::SeqCst))
+        .build()
+        .unwrap();
+    pool.install(|| crate::subgraph("work", 1, || join(|| (), || ())));
+    let logs = logger.extract_logs();
+    // the subgraph's task, both branches, the join's continuation and the subgraph's
+    // continuation
+    let tasks: Vec<_> = logs.thread_events[1]
+        .iter()
+        .filter_map(|e| match e {
+            RawEvent::TaskStart(id, _) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(tasks.len(), 5);
+    // the first measure is taken outside of any task, when the subgraph starts
+    let expected: std::collections::HashMap<_, _> = tasks[..4].iter().cloned().zip(1..).collect();
+    assert_eq!(logs.task_metrics(), expected);
+}
+
+#[cfg(feature = "otel")]
+#[test]
+fn otlp_export() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(5),
+            RawEvent::TaskStart(1, 6),
+            RawEvent::SubgraphStart(0),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::TaskEnd(9),
+        ]],
+        vec!["\"quoted\" work".to_string()],
+    )
+    .unwrap();
+    let mut out = Vec::new();
+    logs.to_otlp(&mut out).unwrap();
+    let json = String::from_utf8(out).unwrap();
+    assert_eq!(json.matches("\"spanId\"").count(), 3);
+    // task 1 is a child of task 0
+    assert!(json.contains(
+        "\"spanId\":\"0000000000000002\",\"parentSpanId\":\"0000000000000001\",\"name\":\"task 1\""
+    ));
+    assert!(json.contains("\"name\":\"\\\"quoted\\\" work\""));
+}
+
+#[test]
+fn fractional_work() {
+    let logger = Logger::new();
+    crate::custom_subgraph_f64("fraction", 0.25, || ());
+    let logs = logger.extract_logs();
+    let works: Vec<f64> = logs.thread_events[0]
+        .iter()
+        .filter_map(|e| e.subgraph_work())
+        .collect();
+    assert_eq!(works, vec![0.25]);
+    assert_eq!(logs.label_timeline("fraction").len(), 1);
+    for compact in [false, true] {
+        let mut buffer = Vec::new();
+        if compact {
+            logs.write_compact_to(&mut buffer).unwrap();
+        } else {
+            logs.write_to(&mut buffer).unwrap();
+        }
+        let (loaded, _) = RawLogs::read_lenient_from(&mut &buffer[..]).unwrap();
+        assert_eq!(loaded, logs);
+    }
+}
+
+#[test]
+fn logs_rotation() {
+    let logger = Logger::new();
+    crate::subgraph("first", 1, || ());
+    let first = logger.take_logs();
+    crate::subgraph("second", 1, || ());
+    let second = logger.take_logs();
+    assert_eq!(first.labels, vec!["first".to_string()]);
+    assert_eq!(second.labels, vec!["second".to_string()]);
+    assert!(second.unterminated_tasks().is_empty());
+}
+
+#[test]
+fn idle_gaps() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::TaskEnd(10),
+                RawEvent::TaskStart(1, 10),
+                RawEvent::TaskEnd(20),
+                RawEvent::TaskStart(2, 35),
+                RawEvent::TaskEnd(40),
+            ],
+            vec![RawEvent::TaskStart(3, 5), RawEvent::TaskEnd(8)],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(logs.idle_gaps(), vec![vec![(20, 35)], vec![]]);
+}
+
+#[test]
+fn blocked_times() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::TaskEnd(10),
+            RawEvent::BlockStart(10),
+            // a stolen task runs while waiting
+            RawEvent::TaskStart(1, 12),
+            RawEvent::TaskEnd(20),
+            RawEvent::BlockEnd(25),
+            RawEvent::TaskStart(2, 25),
+            RawEvent::TaskEnd(30),
+        ]],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(logs.blocked_times(), vec![7]);
+    let mut buffer = Vec::new();
+    logs.write_compact_to(&mut buffer).unwrap();
+    let (loaded, _) = RawLogs::read_lenient_from(&mut &buffer[..]).unwrap();
+    assert_eq!(loaded, logs);
+}
+
+#[test]
+fn numa_nodes() {
+    let logger = Logger::new();
+    let pool = logger
+        .pool_builder()
+        .num_threads(2)
+        .log_numa_nodes(true)
+        .build()
+        .unwrap();
+    pool.install(|| join(|| (), || ()));
+    let logs = logger.extract_logs();
+    // the main thread is not captured
+    assert_eq!(logs.numa_node(0), None);
+    if cfg!(target_os = "linux")
+        && std::path::Path::new("/sys/devices/system/cpu/cpu0/node0").exists()
+    {
+        assert!(logs.numa_node(1).is_some());
+    }
+    assert_eq!(logs.numa_node(3), None);
+}
+
+#[test]
+fn events_sink() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    let subgraphs = Arc::new(AtomicUsize::new(0));
+    let counter = subgraphs.clone();
+    let logger = Logger::with_sink(move |event| {
+        if let RawEvent::SubgraphStart("work") = event {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        join(
+            || crate::subgraph("work", 1, || ()),
+            || crate::subgraph("work", 1, || ()),
+        )
+    });
+    assert_eq!(subgraphs.load(Ordering::SeqCst), 2);
+    assert_eq!(logger.events_count(), 0);
+    // switching to a buffering logger stops forwarding
+    let buffering = Logger::new();
+    crate::subgraph("work", 1, || ());
+    assert_eq!(subgraphs.load(Ordering::SeqCst), 2);
+    assert_eq!(buffering.subgraph_count("work"), 1);
+}
+
+#[test]
+fn events_channel() {
+    use std::sync::mpsc::sync_channel;
+    let (sender, receiver) = sync_channel(2);
+    let logger = Logger::with_channel(sender);
+    let pool = logger.pool_builder().num_threads(1).build().unwrap();
+    pool.install(|| (0..10).for_each(|_| crate::subgraph("work", 1, || ())));
+    let received: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(received.len(), 2);
+    assert!(logger.dropped_events() > 0);
+    // events of the pool carry the index of their thread
+    pool.install(|| crate::subgraph("work", 1, || ()));
+    assert_eq!(receiver.recv().unwrap().0, Some(0));
+    drop(receiver);
+    let dropped = logger.dropped_events();
+    pool.install(|| crate::subgraph("work", 1, || ()));
+    assert!(logger.dropped_events() > dropped);
+    assert_eq!(Logger::new().dropped_events(), 0);
+}
+
+#[test]
+fn parallel_depths() {
+    use crate::ParallelRegion;
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        let _outer = ParallelRegion::enter();
+        join(
+            || {
+                let _inner = ParallelRegion::enter();
+                join(|| (), || ())
+            },
+            || (),
+        )
+    });
+    let logs = logger.extract_logs();
+    let depths = logs.parallel_depths();
+    let mut depths_numbers: Vec<usize> = depths.values().cloned().collect();
+    depths_numbers.sort_unstable();
+    // outer join: 2 branches and continuation, inner join: 2 branches and continuation
+    assert_eq!(depths_numbers, vec![1, 1, 1, 2, 2, 2]);
+    // spawned jobs carry the depth of their creator
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        let _region = ParallelRegion::enter();
+        crate::scope(|s| {
+            s.spawn(|_| {
+                join(|| (), || ());
+            })
+        })
+    });
+    let mut depths: Vec<usize> = logger
+        .extract_logs()
+        .parallel_depths()
+        .into_values()
+        .collect();
+    depths.sort_unstable();
+    assert_eq!(depths, vec![1, 1, 1]);
+    // depths are not maintained when only logging subgraphs
+    let pool = logger.pool_builder().log_tasks(false).build().unwrap();
+    pool.install(|| {
+        let _region = ParallelRegion::enter();
+        assert_eq!(crate::tasks_logs::PARALLEL_DEPTH.with(|d| d.get()), 0);
+    });
+}
+
+#[test]
+fn per_task_values() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::TaskMetric(1),
+            RawEvent::TaskStart(1, 1),
+            RawEvent::TaskMetric(2),
+            RawEvent::TaskEnd(2),
+            // back in task 0, the last value wins
+            RawEvent::TaskMetric(3),
+            RawEvent::TaskEnd(3),
+            RawEvent::TaskStart(2, 4),
+            RawEvent::TaskEnd(5),
+        ]],
+        Vec::new(),
+    )
+    .unwrap();
+    let metrics = logs.per_task(|event| match event {
+        RawEvent::TaskMetric(value) => Some(*value),
+        _ => None,
+    });
+    assert_eq!(metrics, vec![(0, 3), (1, 2)].into_iter().collect());
+    assert_eq!(logs.task_metrics(), metrics);
+}
+
+#[test]
+fn markers() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        Logger::mark("warmup done");
+        join(|| (), || ());
+        Logger::mark("steady state done");
+    });
+    let logs = logger.extract_logs();
+    let markers = logs.markers();
+    assert_eq!(markers.len(), 2);
+    let start = logs.label_id("warmup done").unwrap();
+    assert_eq!(markers[0].1, start);
+    let (start_time, end_time) = logs
+        .markers_bounds("warmup done", "steady state done")
+        .unwrap();
+    assert!(start_time <= end_time);
+    assert_eq!(markers[0].2, start_time);
+    assert!(logs
+        .markers_bounds("steady state done", "missing")
+        .is_none());
+    let mut bytes = Vec::new();
+    logs.write_compact_to(&mut bytes).unwrap();
+    let (loaded, truncated) = RawLogs::read_lenient_from(&mut &bytes[..]).unwrap();
+    assert!(!truncated);
+    assert_eq!(loaded.thread_events, logs.thread_events);
+    assert_eq!(loaded.labels, logs.labels);
+}
+
+#[test]
+fn counters() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        crate::tag_value("queue length", 3);
+        join(|| (), || ());
+        crate::tag_value("queue length", 5);
+    });
+    let logs = logger.extract_logs();
+    // both values share one label
+    let label = logs.label_id("queue length").unwrap();
+    assert_eq!(logs.labels.len(), 1);
+    let counters = logs.counters();
+    assert_eq!(
+        counters
+            .iter()
+            .map(|&(_, label, _, value)| (label, value))
+            .collect::<Vec<_>>(),
+        vec![(label, 3), (label, 5)]
+    );
+    assert!(counters[0].2 <= counters[1].2);
+    let mut bytes = Vec::new();
+    logs.write_compact_to(&mut bytes).unwrap();
+    let (loaded, truncated) = RawLogs::read_lenient_from(&mut &bytes[..]).unwrap();
+    assert!(!truncated);
+    assert_eq!(loaded.thread_events, logs.thread_events);
+}
+
+#[test]
+fn logs_builder() {
+    use crate::tasks_logs::RawLogsBuilder;
+    let mut builder = RawLogsBuilder::new();
+    let events = vec![
+        (0, RawEvent::TaskStart(0, 0)),
+        (0, RawEvent::SubgraphStart("b".to_string())),
+        (1, RawEvent::TaskStart(1, 2)),
+        (1, RawEvent::SubgraphStart("a".to_string())),
+        (1, RawEvent::SubgraphEnd("a".to_string(), 3)),
+        (1, RawEvent::TaskEnd(4)),
+        (0, RawEvent::SubgraphEnd("b".to_string(), 1)),
+        (0, RawEvent::TaskEnd(5)),
+    ];
+    for (thread, event) in events {
+        builder.push_event(thread, event).unwrap();
+    }
+    assert!(builder.push_event(3, RawEvent::TaskEnd(6)).is_err());
+    let logs = builder.build();
+    let expected = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(5),
+            ],
+            vec![
+                RawEvent::TaskStart(1, 2),
+                RawEvent::SubgraphStart(1),
+                RawEvent::SubgraphEnd(1, 3),
+                RawEvent::TaskEnd(4),
+            ],
+        ],
+        vec!["b".to_string(), "a".to_string()],
+    )
+    .unwrap();
+    assert_eq!(logs, expected);
+}
+
+#[test]
+fn task_tree() {
+    // 0 forks 1 and 2, both parents of the continuation 3
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::Child(2),
+                RawEvent::Child(1),
+                RawEvent::TaskEnd(1),
+                RawEvent::TaskStart(1, 2),
+                RawEvent::Child(3),
+                RawEvent::TaskEnd(5),
+            ],
+            vec![
+                RawEvent::TaskStart(2, 3),
+                RawEvent::Child(3),
+                RawEvent::TaskEnd(4),
+                RawEvent::TaskStart(3, 6),
+                RawEvent::TaskEnd(7),
+            ],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    let tree = logs.task_tree();
+    assert_eq!(tree.roots, vec![0]);
+    assert_eq!(tree.children(0), &[1, 2]);
+    assert_eq!(tree.children(1), &[3]);
+    assert!(tree.children(2).is_empty());
+    assert_eq!(tree.parent(3), Some(1));
+    assert_eq!(tree.parent(0), None);
+    assert_eq!(tree.extra_parents[&3], vec![2]);
+    let order: Vec<_> = tree.depth_first().collect();
+    assert_eq!(order, vec![(0, 0), (1, 1), (3, 2), (2, 1)]);
+    assert_eq!(logs.fork_depth_histogram(), vec![1, 2, 1]);
+}
+
+#[test]
+fn save_errors() {
+    use crate::tasks_logs::LogError;
+    use std::io::{self, Write};
+    /// A writer failing once `capacity` bytes were written.
+    struct Full(usize);
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let logs = RawLogs::from_parts(
+        vec![vec![RawEvent::TaskStart(0, 0), RawEvent::TaskEnd(1)]],
+        vec!["label".to_string()],
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    logs.write_to(&mut bytes).unwrap();
+    // fail in the last byte of the second event
+    match logs.write_to(&mut Full(bytes.len() - 1)) {
+        Err(LogError::WritingEvent { thread, index, .. }) => assert_eq!((thread, index), (0, 1)),
+        other => panic!("unexpected result {:?}", other),
+    }
+    // header followed by an empty provenance block
+    let header_len = super::wire_format::HEADER_LEN + 16;
+    match logs.write_compact_to(&mut Full(header_len + 1)) {
+        Err(LogError::WritingLabels(e)) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
+        other => panic!("unexpected result {:?}", other),
+    }
+    // buffered writers are only flushed once, at the end
+    let mut buffered = io::BufWriter::new(Full(header_len + 1));
+    match logs.write_to(&mut buffered) {
+        Err(LogError::Flushing(e)) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
+        other => panic!("unexpected result {:?}", other),
+    }
+    let error: io::Error = logs.write_to(&mut Full(0)).unwrap_err().into();
+    assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+}
+
+#[test]
+fn downsample() {
+    let mut events = Vec::new();
+    // 4 tiny tasks of 1ns in "small", then one 100ns task
+    for id in 0..4 {
+        events.extend(vec![
+            RawEvent::TaskStart(id, 2 * id),
+            RawEvent::SubgraphStart(0),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::TaskEnd(2 * id + 1),
+        ]);
+    }
+    events.extend(vec![RawEvent::TaskStart(4, 8), RawEvent::TaskEnd(108)]);
+    let logs = RawLogs::from_parts(vec![events], vec!["small".to_string()]).unwrap();
+    let downsampled = logs.downsample(10);
+    assert_eq!(
+        downsampled.thread_events[0],
+        vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::MergedTasks(4),
+            RawEvent::SubgraphStart(0),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::TaskEnd(7),
+            RawEvent::TaskStart(4, 8),
+            RawEvent::TaskEnd(108),
+        ]
+    );
+    assert_eq!(downsampled.merged_tasks()[&0], 4);
+    assert_eq!(downsampled.label_timeline("small"), vec![(0, 0, 7)]);
+    // logs under the cap are unchanged, as are the original logs
+    assert_eq!(logs.downsample(100), logs);
+    assert_eq!(logs.thread_events[0].len(), 18);
+    // the cap cannot always be reached
+    assert_eq!(logs.downsample(1).thread_events, downsampled.thread_events);
+}
+
+#[test]
+fn task_priorities() {
+    use crate::tasks_logs::log_task_priority;
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(1).build().unwrap();
+    pool.install(|| {
+        join(
+            || log_task_priority(5),
+            // the default priority is not recorded
+            || log_task_priority(0),
+        )
+    });
+    let logs = logger.extract_logs();
+    let priorities = logs.task_priorities();
+    assert_eq!(priorities.values().cloned().collect::<Vec<_>>(), vec![5]);
+    // priorities above 255 are rejected when loading
+    let mut bytes = RawEvent::TaskPriority(5).to_bytes();
+    bytes[2] = 1;
+    assert!(RawEvent::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn ci_line() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![RawEvent::TaskStart(0, 0), RawEvent::TaskEnd(4_000_000)],
+            vec![
+                RawEvent::TaskStart(1, 1_000_000),
+                RawEvent::TaskEnd(4_000_000),
+            ],
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(
+        logs.ci_line(),
+        "RAYON_SUMMARY threads=2 span_ms=4 work_ms=7 speedup=1.8 eff=0.88"
+    );
+    let empty = RawLogs::from_parts(Vec::new(), Vec::new()).unwrap();
+    assert_eq!(
+        empty.ci_line(),
+        "RAYON_SUMMARY threads=0 span_ms=0 work_ms=0 speedup=0.0 eff=0.00"
+    );
+}
+
+#[test]
+fn phases() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        Logger::begin_phase("load");
+        join(|| (), || ());
+        Logger::begin_phase("compute");
+        join(|| (), || ());
+    });
+    let logs = logger.extract_logs();
+    let phases = logs.phases();
+    let names: Vec<_> = phases
+        .iter()
+        .map(|&(label, _, _)| logs.label(label).unwrap())
+        .collect();
+    assert_eq!(names, vec!["load", "compute"]);
+    assert_eq!(phases[0].2, phases[1].1);
+    assert!(phases[1].1 <= phases[1].2);
+    let mut bytes = Vec::new();
+    logs.write_to(&mut bytes).unwrap();
+    let (loaded, _) = RawLogs::read_lenient_from(&mut &bytes[..]).unwrap();
+    assert_eq!(loaded.phases(), phases);
+    assert!(RawLogs::from_parts(vec![vec![RawEvent::PhaseStart(0, 0)]], Vec::new()).is_err());
+}
+
+#[test]
+fn insert_event() {
+    let mut logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(10),
+            RawEvent::TaskStart(1, 20),
+            RawEvent::TaskEnd(30),
+        ]],
+        vec!["gc".to_string()],
+    )
+    .unwrap();
+    logs.insert_event(0, RawEvent::Marker(0, 10)).unwrap();
+    logs.insert_event(0, RawEvent::BlockStart(12)).unwrap();
+    logs.insert_event(0, RawEvent::BlockEnd(18)).unwrap();
+    logs.insert_event(0, RawEvent::Marker(0, 40)).unwrap();
+    assert_eq!(
+        logs.thread_events[0],
+        vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(10),
+            RawEvent::Marker(0, 10),
+            RawEvent::BlockStart(12),
+            RawEvent::BlockEnd(18),
+            RawEvent::TaskStart(1, 20),
+            RawEvent::TaskEnd(30),
+            RawEvent::Marker(0, 40),
+        ]
+    );
+    assert!(logs.insert_event(1, RawEvent::BlockStart(0)).is_err());
+    assert!(logs.insert_event(0, RawEvent::Child(3)).is_err());
+    assert!(logs.insert_event(0, RawEvent::Marker(1, 5)).is_err());
+    let mut unsorted = RawLogs::from_parts(
+        vec![vec![RawEvent::TaskEnd(10), RawEvent::TaskEnd(5)]],
+        Vec::new(),
+    )
+    .unwrap();
+    assert!(unsorted.insert_event(0, RawEvent::BlockStart(7)).is_err());
+}
+
+#[test]
+fn manual_clock() {
+    use crate::tasks_logs::ManualClock;
+    use std::sync::Arc;
+    let run = || {
+        let clock = Arc::new(ManualClock::new());
+        let logger = Logger::with_manual_clock(clock.clone());
+        let pool = logger.single_thread_pool_builder().build().unwrap();
+        pool.install(|| {
+            crate::subgraph("work", 1, || {
+                join(|| clock.advance(3), || join(|| clock.advance(4), || ()))
+            })
+        });
+        clock.advance(1);
+        logger.extract_logs().thread_events
+    };
+    // times and tasks ids are reproducible
+    let first_run = run();
+    assert_eq!(first_run, run());
+    // the main task ends after the pool's work
+    let main_times: Vec<_> = first_run[0].iter().map(RawEvent::time).collect();
+    assert_eq!(main_times, vec![Some(0), Some(8)]);
+}
+
+#[test]
+fn dropped_logger_detaches_threads() {
+    use std::sync::mpsc::{sync_channel, TryRecvError};
+    let (sender, receiver) = sync_channel(100);
+    let logger = Logger::with_channel(sender);
+    crate::subgraph("work", 1, || ());
+    drop(logger);
+    crate::subgraph("work", 1, || ());
+    // the sink (and its sender) went away with the logger
+    assert!(receiver.try_iter().count() > 0);
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+}
+
+#[test]
+fn coalesce_subgraphs() {
+    let mut logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(10),
+            RawEvent::TaskStart(1, 10),
+            RawEvent::SubgraphEnd(0, 2),
+            // restarts right away
+            RawEvent::Child(2),
+            RawEvent::TaskEnd(12),
+            RawEvent::TaskStart(2, 12),
+            RawEvent::SubgraphStart(0),
+            // empty but lasting until the end of the task
+            RawEvent::SubgraphStart(1),
+            RawEvent::SubgraphEnd(1, 1),
+            RawEvent::Child(3),
+            RawEvent::TaskEnd(20),
+            RawEvent::TaskStart(3, 20),
+            RawEvent::SubgraphEndF64(0, 0.5f64.to_bits()),
+            // empty and not lasting
+            RawEvent::SubgraphStart(1),
+            RawEvent::SubgraphEnd(1, 1),
+            RawEvent::TaskEnd(20),
+            RawEvent::TaskStart(4, 100),
+            // restarts after a long gap
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(5),
+            RawEvent::SubgraphEnd(0, 1),
+            // restarts after an unknown gap
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(6),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::TaskEnd(110),
+        ]],
+        vec!["work".to_string(), "noise".to_string()],
+    )
+    .unwrap();
+    logs.coalesce_subgraphs(10);
+    assert_eq!(
+        logs.thread_events[0],
+        vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(10),
+            RawEvent::TaskStart(1, 10),
+            RawEvent::Child(2),
+            RawEvent::TaskEnd(12),
+            RawEvent::TaskStart(2, 12),
+            RawEvent::SubgraphStart(1),
+            RawEvent::SubgraphEnd(1, 1),
+            RawEvent::Child(3),
+            RawEvent::TaskEnd(20),
+            RawEvent::TaskStart(3, 20),
+            RawEvent::SubgraphEndF64(0, 2.5f64.to_bits()),
+            RawEvent::TaskEnd(20),
+            RawEvent::TaskStart(4, 100),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(5),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(6),
+            RawEvent::SubgraphEnd(0, 1),
+            RawEvent::TaskEnd(110),
+        ]
+    );
+    assert!(logs.validate().is_empty());
+    // with a large enough gap everything with a known gap is merged
+    logs.coalesce_subgraphs(100);
+    let report = logs.subgraph_report();
+    assert_eq!((report[0].count, report[0].work), (2, 4.5));
+    assert_eq!(report[1].count, 1);
+}
+
+#[test]
+fn coalesce_subgraphs_without_tasks() {
+    use crate::tasks_logs::ManualClock;
+    use std::sync::Arc;
+    let clock = Arc::new(ManualClock::new());
+    let logger = Logger::with_manual_clock(clock.clone());
+    let pool = logger
+        .pool_builder()
+        .num_threads(1)
+        .log_tasks(false)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        crate::subgraph("work", 1, || clock.advance(10));
+        crate::subgraph("work", 1, || clock.advance(10));
+        crate::subgraph("empty", 1, || ());
+        clock.advance(100);
+        crate::subgraph("work", 1, || clock.advance(10));
+        crate::subgraph("empty", 1, || clock.advance(5));
+    });
+    let mut logs = logger.extract_logs();
+    // each subgraph runs in its own task
+    assert_eq!(logs.label_timeline("work").len(), 3);
+    assert_eq!(logs.label_timeline("empty").len(), 2);
+    logs.coalesce_subgraphs(10);
+    assert!(logs.validate().is_empty());
+    // the first two executions are merged across the tasks ending and starting them
+    assert_eq!(
+        logs.label_timeline("work")
+            .iter()
+            .map(|&(_, start, end)| (start, end))
+            .collect::<Vec<_>>(),
+        vec![(0, 20), (120, 130)]
+    );
+    // only the execution which did not last is dropped
+    assert_eq!(logs.label_timeline("empty").len(), 1);
+}
+
+#[test]
+fn filter_kinds() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::TaskPriority(2),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Marker(1, 3),
+            RawEvent::Child(1),
+            RawEvent::SubgraphEnd(0, 4),
+            RawEvent::TaskEnd(10),
+        ]],
+        vec!["work".to_string(), "mark".to_string()],
+    )
+    .unwrap();
+    let markers = logs.filter_kinds(EventKinds::MARKER | EventKinds::CHILD);
+    assert_eq!(
+        markers.thread_events[0],
+        vec![RawEvent::Marker(1, 3), RawEvent::Child(1)]
+    );
+    assert_eq!(markers.labels, logs.labels);
+    // ends of subgraphs bring their starts and the tasks timing them
+    let subgraphs = logs.filter_kinds(EventKinds::SUBGRAPH_END);
+    assert_eq!(
+        subgraphs.thread_events[0],
+        vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::SubgraphStart(0),
+            RawEvent::SubgraphEnd(0, 4),
+            RawEvent::TaskEnd(10),
+        ]
+    );
+    assert!(subgraphs.validate().is_empty());
+    assert_eq!(subgraphs.label_timeline("work"), vec![(0, 0, 10)]);
+    // priorities stay attached to their tasks
+    let priorities = logs.filter_kinds(EventKinds::TASK_PRIORITY);
+    assert_eq!(
+        priorities.thread_events[0],
+        vec![
+            RawEvent::TaskStart(0, 0),
+            RawEvent::TaskPriority(2),
+            RawEvent::TaskEnd(10),
+        ]
+    );
+    assert_eq!(priorities.task_priorities()[&0], 2);
+    assert_eq!(
+        logs.filter_kinds(EventKinds::TASK_END).thread_events[0].len(),
+        2
+    );
+    assert_eq!(logs.filter_kinds(EventKinds::ALL), logs);
+    assert!(logs.filter_kinds(EventKinds::NONE).thread_events[0].is_empty());
+}
+
+#[test]
+fn provenance() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(1).build().unwrap();
+    pool.install(|| crate::subgraph("work", 1, || ()));
+    let logs = logger.extract_logs();
+    let provenance = logs.provenance().unwrap();
+    assert_eq!(provenance.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        provenance.flags.contains(&"debug_assertions".to_string()),
+        cfg!(debug_assertions)
+    );
+    for compact in &[false, true] {
+        let mut bytes = Vec::new();
+        if *compact {
+            logs.write_compact_to(&mut bytes).unwrap();
+        } else {
+            logs.write_to(&mut bytes).unwrap();
+        }
+        let (loaded, _) = RawLogs::read_lenient_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded.provenance(), Some(provenance));
+        assert_eq!(loaded.thread_events, logs.thread_events);
+    }
+    // logs built by hand have no provenance and are saved with an empty block
+    let built = RawLogs::from_parts(vec![vec![RawEvent::TaskStart(0, 1)]], Vec::new()).unwrap();
+    let mut bytes = Vec::new();
+    built.write_to(&mut bytes).unwrap();
+    let (loaded, _) = RawLogs::read_lenient_from(&mut bytes.as_slice()).unwrap();
+    assert_eq!(loaded.provenance(), None);
+    assert_eq!(loaded, built);
+    assert_eq!(bytes.len(), built.encoded_size());
+    // the block is mandatory after a header
+    let header_len = super::wire_format::HEADER_LEN;
+    let mut unmarked = bytes[..header_len].to_vec();
+    unmarked.extend_from_slice(&bytes[header_len + 8..]);
+    let error = RawLogs::read_lenient_from(&mut unmarked.as_slice()).unwrap_err();
+    assert_eq!(error.io_error().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn encoded_size() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| {
+        crate::custom_subgraph_with_unit("work", "bytes", || 0, |_| 3, || join(|| (), || ()))
+    });
+    let mut logs = logger.extract_logs();
+    logs.units.push(None);
+    logs.labels.push("ünïcode".to_string());
+    logs.thread_events.push(Vec::new());
+    logs.thread_events[0].push(RawEvent::SubgraphEndF64(0, 1.5f64.to_bits()));
+    let mut bytes = Vec::new();
+    logs.write_to(&mut bytes).unwrap();
+    assert_eq!(logs.encoded_size(), bytes.len());
+    let built = RawLogs::from_parts(vec![vec![RawEvent::TaskStart(0, 1)]], Vec::new()).unwrap();
+    bytes.clear();
+    built.write_to(&mut bytes).unwrap();
+    assert_eq!(built.encoded_size(), bytes.len());
+    assert_eq!(built.event_count(), 1);
+    assert_eq!(
+        logs.event_count(),
+        logs.thread_events.iter().map(Vec::len).sum::<usize>()
+    );
+}
+
+#[test]
+fn load() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 1),
+                RawEvent::SubgraphStart(0),
+                RawEvent::Child(1),
+                RawEvent::SubgraphEnd(0, 3),
+                RawEvent::TaskEnd(5),
+            ],
+            Vec::new(),
+            vec![RawEvent::TaskStart(1, 6), RawEvent::TaskEnd(7)],
+        ],
+        vec!["work".to_string()],
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    logs.write_to(&mut bytes).unwrap();
+    assert_eq!(RawLogs::read_from(&mut bytes.as_slice()).unwrap(), logs);
+    let mut compact = Vec::new();
+    logs.write_compact_to(&mut compact).unwrap();
+    assert_eq!(RawLogs::read_from(&mut compact.as_slice()).unwrap(), logs);
+    // logs recorded by a logger
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    pool.install(|| crate::subgraph("work", 2, || join(|| (), || ())));
+    let recorded = logger.extract_logs();
+    let mut saved = Vec::new();
+    recorded.write_to(&mut saved).unwrap();
+    let loaded = RawLogs::read_from(&mut saved.as_slice()).unwrap();
+    assert_eq!(loaded.labels, vec!["work".to_string()]);
+    assert_eq!(loaded.thread_events, recorded.thread_events);
+    // truncated logs are invalid
+    let error = RawLogs::read_from(&mut &bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(error.io_error().kind(), std::io::ErrorKind::InvalidData);
+    // and so are truncated events
+    let event = RawEvent::TaskStart(1, 6).to_bytes();
+    let error = RawEvent::read_from(&mut &event[..event.len() - 1]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    let error = RawEvent::read_from(&mut &[127u8][..]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    // missing files
+    let error = RawLogs::load("/nonexistent/rayon.rlog").unwrap_err();
+    assert_eq!(error.io_error().kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn chrome_trace() {
+    let logs = RawLogs::from_parts(
+        vec![vec![
+            RawEvent::TaskStart(0, 1_000),
+            RawEvent::SubgraphStart(0),
+            RawEvent::Child(1),
+            RawEvent::TaskEnd(3_500),
+            RawEvent::TaskStart(1, 4_000),
+            RawEvent::SubgraphEnd(0, 7),
+            RawEvent::TaskEnd(5_000),
+        ]],
+        vec!["so\"rt".to_string()],
+    )
+    .unwrap();
+    let mut json = Vec::new();
+    logs.write_chrome_trace(&mut json).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        "[{\"name\":\"task 0\",\"cat\":\"task\",\"ph\":\"X\",\"ts\":1,\"dur\":2.5,\"pid\":0,\"tid\":0},\
+         {\"name\":\"task 1\",\"cat\":\"task\",\"ph\":\"X\",\"ts\":4,\"dur\":1,\"pid\":0,\"tid\":0},\
+         {\"name\":\"so\\\"rt\",\"cat\":\"subgraph\",\"ph\":\"X\",\"ts\":1,\"dur\":4,\"pid\":0,\"tid\":0,\
+         \"args\":{\"work\":7}}]"
+    );
+}
+
+#[test]
+fn statistics() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 10),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphStart(1),
+                RawEvent::SubgraphEnd(1, 2),
+                // a task nested in the first one is not counted twice
+                RawEvent::TaskStart(1, 15),
+                RawEvent::TaskEnd(18),
+                RawEvent::SubgraphEnd(0, 5),
+                RawEvent::TaskEnd(20),
+                RawEvent::TaskStart(2, 30),
+                RawEvent::SubgraphStart(0),
+                RawEvent::SubgraphEndF64(0, 0.5f64.to_bits()),
+                RawEvent::TaskEnd(40),
+            ],
+            Vec::new(),
+            vec![
+                RawEvent::PhaseStart(2, 0),
+                RawEvent::TaskStart(3, 0),
+                RawEvent::PhaseStart(3, 25),
+                RawEvent::TaskEnd(50),
+            ],
+        ],
+        vec![
+            "outer".to_string(),
+            "inner".to_string(),
+            "load".to_string(),
+            "run".to_string(),
+        ],
+    )
+    .unwrap();
+    let statistics = logs.statistics();
+    assert_eq!(statistics.span, 50);
+    assert_eq!(statistics.threads.len(), 3);
+    let first = &statistics.threads[0];
+    assert_eq!((first.busy_time, first.idle_time, first.tasks), (20, 10, 3));
+    assert_eq!(first.works[&0], 5.5);
+    assert_eq!(first.works[&1], 2.0);
+    assert_eq!(statistics.threads[1], ThreadStatistics::default());
+    assert_eq!(statistics.threads[2].busy_time, 50);
+    assert_eq!(statistics.parallel_efficiency(), 70.0 / 150.0);
+    // busy times are split between phases
+    let phases: Vec<_> = statistics
+        .phases
+        .iter()
+        .map(|phase| (phase.label, phase.duration, phase.busy_time))
+        .collect();
+    assert_eq!(phases, vec![(2, 25, 35), (3, 25, 35)]);
+}
+
+#[test]
+fn cloned_loggers() {
+    let logger = Logger::new();
+    let clone = logger.clone();
+    let pool = clone.pool_builder().num_threads(1).build().unwrap();
+    pool.install(|| crate::subgraph("work", 1, || ()));
+    assert_eq!(logger.extract_logs().labels, vec!["work".to_string()]);
+}
+
+#[test]
+fn labeled_main_task() {
+    let logger = Logger::new_labeled("main");
+    crate::subgraph("work", 1, || ());
+    let logs = logger.take_logs();
+    assert_eq!(logs.labels, vec!["main".to_string(), "work".to_string()]);
+    let main_spans = logs.label_timeline("main");
+    assert_eq!(main_spans.len(), 1);
+    assert_eq!(main_spans[0].0, 0);
+    assert_eq!(logger.extract_logs().label_timeline("main").len(), 1);
+}
+
+#[test]
+fn reset_from_another_thread() {
+    let logger = std::sync::Arc::new(Logger::new_labeled("main"));
+    let resetter = logger.clone();
+    std::thread::spawn(move || resetter.reset()).join().unwrap();
+    // the new main task is kept by the logger, not written in the main thread's storage
+    assert_eq!(logger.events_count(), 2);
+    assert_eq!(logger.subgraph_count("main"), 1);
+    assert_eq!(logger.live_label_activity()["main"], 1);
+    crate::subgraph("work", 1, || ());
+    let logs = logger.take_logs();
+    assert!(logs.validate().is_empty());
+    assert!(matches!(logs.thread_events[0][0], RawEvent::TaskStart(..)));
+    assert_eq!(logs.label_timeline("main").len(), 1);
+    assert_eq!(logs.label_timeline("work").len(), 1);
+    // the main task is also flushed with the main thread
+    let flushed = logger.flush_thread(None).unwrap();
+    assert_eq!(flushed.labels, vec!["main".to_string()]);
+    assert_eq!(logger.events_count(), 0);
+}
+
+#[test]
+fn sweep() {
+    let logs = RawLogs::from_parts(
+        vec![
+            vec![
+                RawEvent::TaskStart(0, 0),
+                RawEvent::SubgraphStart(0),
+                RawEvent::Child(1),
+                RawEvent::SubgraphEnd(0, 1),
+                RawEvent::TaskEnd(10),
+            ],
+            vec![RawEvent::TaskStart(1, 5), RawEvent::TaskEnd(20)],
+        ],
+        vec!["a".to_string()],
+    )
+    .unwrap();
+    let mut states = Vec::new();
+    logs.sweep(vec![0, 5, 10, 20].into_iter(), |time, state| {
+        states.push((
+            time,
+            state.busy_threads(),
+            state.active_tasks(1).to_vec(),
+            state.active_subgraphs(0).to_vec(),
+        ))
+    });
+    assert_eq!(
+        states,
+        vec![
+            (0, 1, vec![], vec![0]),
+            (5, 2, vec![1], vec![0]),
+            (10, 1, vec![1], vec![]),
+            (20, 0, vec![], vec![]),
+        ]
+    );
+}
+
+#[test]
+fn empty_logs() {
+    let logger = Logger::new();
+    let pool = logger.pool_builder().num_threads(2).build().unwrap();
+    assert_eq!(logger.current_span(), 0);
+    let near_empty = logger.extract_logs();
+    drop(pool);
+    let empty = RawLogs::from_parts(Vec::new(), Vec::new()).unwrap();
+    for logs in &[near_empty, empty] {
+        assert_eq!(logs.grain_report(1_000).small_fraction(), 0.0);
+        assert!(logs
+            .occupancy_by_label(10)
+            .iter()
+            .all(|(_, l)| l.is_empty()));
+        assert!(logs.queue_depth_timeline(10).iter().all(|&(_, d)| d == 0));
+        assert!(logs.idle_gaps().iter().all(|gaps| gaps.is_empty()));
+        assert!(logs.label_timeline("nothing").is_empty());
+        assert!(logs.unterminated_tasks().is_empty());
+        let mut out = Vec::new();
+        logs.dump(&mut out).unwrap();
+        logs.write_to(&mut out).unwrap();
+        logs.write_compact_to(&mut out).unwrap();
+        #[cfg(feature = "otel")]
+        {
+            let mut json = Vec::new();
+            logs.to_otlp(&mut json).unwrap();
+            assert!(String::from_utf8(json).unwrap().ends_with("]}]}]}"));
+        }
+    }
+}
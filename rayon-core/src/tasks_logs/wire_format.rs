@@ -0,0 +1,623 @@
+//! Binary format of saved logs.
+//!
+//! Logs files are written by `Logger::save_raw_logs` and can be read by external
+//! tools, possibly written in other languages, against the following contract.
+//!
+//! A file starts with a header: the `MAGIC` bytes followed by a format byte
+//! (`FORMAT_FIXED` or `FORMAT_COMPACT`) and by `BYTE_ORDER_MARK` written as a little
+//! endian `u64`.
+//! Older files have no header and are always in the fixed format
+//! (they can be recognized since they never start with `MAGIC`).
+//!
+//! Files are always little endian, whatever the endianness of the machine writing or
+//! reading them. The byte order mark allows rejecting files written in another byte
+//! order by a foreign tool instead of silently misreading them.
+//!
+//! The header is followed by a provenance block: the `PROVENANCE_MARK` bytes followed
+//! by a list of strings (written like the labels), the rayon version which recorded the
+//! logs and then its build flags. The list is empty if the provenance is unknown.
+//!
+//! In the fixed format all integers are `u64` written in little endian.
+//! In both formats strings are written as their byte length followed by their utf-8 bytes.
+//! After the header, a file contains, in order:
+//!
+//! - the labels: their number followed by each label string;
+//! - the labels units: their number (same as labels) followed by each unit string
+//!   (empty if the label has no unit);
+//! - the number of threads;
+//! - for each thread, its number of events followed by each event.
+//!
+//! An event is a tag byte followed by its fields:
+//!
+//! | tag                    | event            | fields                     |
+//! |------------------------|------------------|----------------------------|
+//! | `TAG_TASK_START`       | `TaskStart`      | task id, time              |
+//! | `TAG_TASK_END`         | `TaskEnd`        | time                       |
+//! | `TAG_CHILD`            | `Child`          | task id                    |
+//! | `TAG_SUBGRAPH_START`   | `SubgraphStart`  | label id                   |
+//! | `TAG_SUBGRAPH_END`     | `SubgraphEnd`    | label id, work amount      |
+//! | `TAG_TASK_METRIC`      | `TaskMetric`     | value                      |
+//! | `TAG_SUBGRAPH_END_F64` | `SubgraphEndF64` | label id, work amount bits |
+//! | `TAG_BLOCK_START`      | `BlockStart`     | time                       |
+//! | `TAG_BLOCK_END`        | `BlockEnd`       | time                       |
+//! | `TAG_PARALLEL_DEPTH`   | `ParallelDepth`  | depth                      |
+//! | `TAG_MARKER`           | `Marker`         | label id, time             |
+//! | `TAG_MERGED_TASKS`     | `MergedTasks`    | count                      |
+//! | `TAG_TASK_PRIORITY`    | `TaskPriority`   | priority                   |
+//! | `TAG_PHASE_START`      | `PhaseStart`     | label id, time             |
+//! | `TAG_COUNTER`          | `Counter`        | label id, value, time      |
+//!
+//! Tags in `USER_TAGS` will never be used by rayon and are reserved for user events.
+//!
+//! The compact format only differs in events and in threads and events numbers:
+//! these integers are written as LEB128 varints (7 bits per byte, least significant
+//! first, high bit set on all bytes but the last).
+//! Times are also delta-encoded: each time is stored as the zigzag encoded difference
+//! with the previous time of the same thread (0 for the first time of each thread).
+//! Bits of `f64` work amounts are still written as fixed size `u64`.
+//! Labels sections are the same in both formats.
+use super::{Provenance, RawEvent, SubGraphId, TimeStamp};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::ops::RangeInclusive;
+
+/// Tag of `RawEvent::TaskStart`.
+pub const TAG_TASK_START: u8 = 2;
+/// Tag of `RawEvent::TaskEnd`.
+pub const TAG_TASK_END: u8 = 3;
+/// Tag of `RawEvent::Child`.
+pub const TAG_CHILD: u8 = 4;
+/// Tag of `RawEvent::SubgraphStart`.
+pub const TAG_SUBGRAPH_START: u8 = 5;
+/// Tag of `RawEvent::SubgraphEnd`.
+pub const TAG_SUBGRAPH_END: u8 = 6;
+/// Tag of `RawEvent::TaskMetric`.
+pub const TAG_TASK_METRIC: u8 = 7;
+/// Tag of `RawEvent::SubgraphEndF64`.
+pub const TAG_SUBGRAPH_END_F64: u8 = 8;
+/// Tag of `RawEvent::BlockStart`.
+pub const TAG_BLOCK_START: u8 = 9;
+/// Tag of `RawEvent::BlockEnd`.
+pub const TAG_BLOCK_END: u8 = 10;
+/// Tag of `RawEvent::ParallelDepth`.
+pub const TAG_PARALLEL_DEPTH: u8 = 11;
+/// Tag of `RawEvent::Marker`.
+pub const TAG_MARKER: u8 = 12;
+/// Tag of `RawEvent::MergedTasks`.
+pub const TAG_MERGED_TASKS: u8 = 13;
+/// Tag of `RawEvent::TaskPriority`.
+pub const TAG_TASK_PRIORITY: u8 = 14;
+/// Tag of `RawEvent::PhaseStart`.
+pub const TAG_PHASE_START: u8 = 15;
+/// Tag of `RawEvent::Counter`.
+pub const TAG_COUNTER: u8 = 16;
+/// Tags reserved for user events.
+pub const USER_TAGS: RangeInclusive<u8> = 128..=255;
+
+/// First bytes of all logs files (but old ones).
+pub const MAGIC: [u8; 8] = *b"RAYONLOG";
+/// Written after the format byte, in little endian, to check the byte order of files.
+pub const BYTE_ORDER_MARK: u64 = 0x0102_0304_0506_0708;
+/// First bytes of the provenance block, after the header.
+pub const PROVENANCE_MARK: [u8; 8] = *b"RAYONPRV";
+/// Format byte of files with fixed size integers.
+pub const FORMAT_FIXED: u8 = 1;
+/// Format byte of files with varints and delta-encoded times.
+pub const FORMAT_COMPACT: u8 = 2;
+
+/// Number of bytes of the header (with its byte order mark).
+pub(super) const HEADER_LEN: usize = MAGIC.len() + 1 + 8;
+
+pub(super) fn write_header<W: Write>(format: u8, destination: &mut W) -> io::Result<()> {
+    destination.write_all(&MAGIC)?;
+    destination.write_all(&[format])?;
+    write_u64(BYTE_ORDER_MARK, destination)
+}
+
+/// Read the file header and return the file's format.
+/// Files without header (always in the fixed format) return `None` and nothing is
+/// consumed.
+/// Fails with `InvalidData` if the file is not little endian (or has no byte order mark).
+pub(super) fn read_header<R: BufRead>(source: &mut R) -> io::Result<Option<u8>> {
+    if !source.fill_buf()?.starts_with(&MAGIC) {
+        return Ok(None);
+    }
+    source.consume(MAGIC.len());
+    let mut format = [0u8];
+    source.read_exact(&mut format)?;
+    match format[0] {
+        FORMAT_FIXED | FORMAT_COMPACT => (),
+        format => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown logs format {}", format),
+            ))
+        }
+    }
+    let mut mark = [0u8; 8];
+    source.read_exact(&mut mark)?;
+    if mark == BYTE_ORDER_MARK.to_be_bytes() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "big endian logs are not supported",
+        ));
+    } else if mark != BYTE_ORDER_MARK.to_le_bytes() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing byte order mark",
+        ));
+    }
+    Ok(Some(format[0]))
+}
+
+/// Write the provenance block (with no strings if the provenance is unknown).
+pub(super) fn write_provenance<W: Write>(
+    provenance: Option<&Provenance>,
+    destination: &mut W,
+) -> io::Result<()> {
+    destination.write_all(&PROVENANCE_MARK)?;
+    let strings: Vec<String> = provenance
+        .map(|provenance| {
+            std::iter::once(&provenance.version)
+                .chain(&provenance.flags)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    write_vec_strings_to(&strings, destination)
+}
+
+/// Read the provenance block following the header.
+/// Fails with `InvalidData` if it does not start with `PROVENANCE_MARK`.
+pub(super) fn read_provenance<R: Read>(source: &mut R) -> io::Result<Option<Provenance>> {
+    let mut mark = [0u8; 8];
+    source.read_exact(&mut mark)?;
+    if mark != PROVENANCE_MARK {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing provenance block",
+        ));
+    }
+    let mut strings = read_vec_strings_from(source)?.into_iter();
+    Ok(strings.next().map(|version| Provenance {
+        version,
+        flags: strings.collect(),
+    }))
+}
+
+/// Payload of the `InvalidData` errors of truncated events.
+#[derive(Debug)]
+struct Truncated;
+
+impl fmt::Display for Truncated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "truncated event")
+    }
+}
+
+impl Error for Truncated {}
+
+/// Turn the end of input in the middle of an event into an `InvalidData` error.
+fn truncation(error: io::Error) -> io::Error {
+    if error.kind() == io::ErrorKind::UnexpectedEof {
+        io::Error::new(io::ErrorKind::InvalidData, Truncated)
+    } else {
+        error
+    }
+}
+
+/// Did reading fail because the input ended (in an event or elsewhere).
+pub(super) fn is_truncation(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::UnexpectedEof
+        || matches!(error.get_ref(), Some(inner) if inner.is::<Truncated>())
+}
+
+// little endian read
+pub(super) fn read_u64<R: Read>(source: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    source.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Check a priority read as an integer fits in a byte.
+fn read_priority(priority: u64) -> io::Result<u8> {
+    u8::try_from(priority).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid priority {}", priority),
+        )
+    })
+}
+
+pub(super) fn read_vec_strings_from<R: Read>(source: &mut R) -> io::Result<Vec<String>> {
+    let length = read_u64(source)?;
+    (0..length)
+        .map(|_| {
+            let bytes_number = read_u64(source)?;
+            let mut bytes = Vec::new();
+            source.take(bytes_number).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != bytes_number {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .collect()
+}
+
+// LEB128 read
+pub(super) fn read_varint<R: Read>(source: &mut R) -> io::Result<u64> {
+    let mut integer = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        source.read_exact(&mut byte)?;
+        integer |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(integer);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint is too long",
+    ))
+}
+
+// LEB128 write
+pub(super) fn write_varint<W: Write>(mut integer: u64, destination: &mut W) -> io::Result<()> {
+    let mut bytes = [0u8; 10];
+    let mut length = 0;
+    loop {
+        let byte = (integer & 0x7f) as u8;
+        integer >>= 7;
+        if integer == 0 {
+            bytes[length] = byte;
+            length += 1;
+            break;
+        }
+        bytes[length] = byte | 0x80;
+        length += 1;
+    }
+    destination.write_all(&bytes[..length])
+}
+
+/// Read a delta-encoded time.
+fn read_time<R: Read>(previous_time: &mut TimeStamp, source: &mut R) -> io::Result<TimeStamp> {
+    let zigzag = read_varint(source)?;
+    let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+    *previous_time = previous_time.wrapping_add(delta as u64);
+    Ok(*previous_time)
+}
+
+/// Write a delta-encoded time.
+fn write_time<W: Write>(
+    time: TimeStamp,
+    previous_time: &mut TimeStamp,
+    destination: &mut W,
+) -> io::Result<()> {
+    let delta = time.wrapping_sub(*previous_time) as i64;
+    *previous_time = time;
+    write_varint(((delta << 1) ^ (delta >> 63)) as u64, destination)
+}
+
+// little endian write
+pub(super) fn write_u64<W: Write>(integer: u64, destination: &mut W) -> io::Result<()> {
+    destination.write_all(&integer.to_le_bytes())
+}
+
+/// Number of bytes written by `write_vec_strings_to` for given strings.
+pub(super) fn vec_strings_len<'a>(strings: impl Iterator<Item = &'a str>) -> usize {
+    8 + strings.map(|string| 8 + string.len()).sum::<usize>()
+}
+
+pub(super) fn write_vec_strings_to<W: Write>(
+    vector: &[String],
+    destination: &mut W,
+) -> io::Result<()> {
+    // write the length
+    write_u64(vector.len() as u64, destination)?;
+    // write for each string its byte size and then all bytes
+    for string in vector {
+        let bytes = string.as_bytes();
+        write_u64(bytes.len() as u64, destination)?;
+        destination.write_all(bytes)?;
+    }
+    Ok(())
+}
+
+impl RawEvent<SubGraphId> {
+    /// Number of bytes of this event in the fixed format: its tag followed by 8 bytes
+    /// for each field.
+    pub fn encoded_len(&self) -> usize {
+        let fields = match self {
+            RawEvent::TaskEnd(_)
+            | RawEvent::Child(_)
+            | RawEvent::TaskMetric(_)
+            | RawEvent::SubgraphStart(_)
+            | RawEvent::BlockStart(_)
+            | RawEvent::BlockEnd(_)
+            | RawEvent::ParallelDepth(_)
+            | RawEvent::MergedTasks(_)
+            | RawEvent::TaskPriority(_) => 1,
+            RawEvent::TaskStart(..)
+            | RawEvent::SubgraphEnd(..)
+            | RawEvent::SubgraphEndF64(..)
+            | RawEvent::Marker(..)
+            | RawEvent::PhaseStart(..) => 2,
+            RawEvent::Counter(..) => 3,
+        };
+        1 + 8 * fields
+    }
+    /// Encode this event in the fixed format (as in saved files), for example to frame
+    /// it in a network protocol.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        self.write_to(&mut bytes)
+            .expect("writing to a vector cannot fail");
+        bytes
+    }
+    /// Decode an event encoded with `to_bytes`, failing with `InvalidData` on unknown tags,
+    /// truncated bytes or bytes left after the event.
+    pub fn from_bytes(mut bytes: &[u8]) -> io::Result<RawEvent<SubGraphId>> {
+        let event = RawEvent::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} bytes after the event", bytes.len()),
+            ));
+        }
+        Ok(event)
+    }
+    /// Read one event in the fixed format (the inverse of `to_bytes`), failing with
+    /// `InvalidData` on unknown tags or truncated input.
+    pub fn read_from<R: Read>(source: &mut R) -> io::Result<RawEvent<SubGraphId>> {
+        RawEvent::decode(source).map_err(truncation)
+    }
+    fn decode<R: Read>(source: &mut R) -> io::Result<RawEvent<SubGraphId>> {
+        let mut tag = [0u8];
+        source.read_exact(&mut tag)?;
+        let event = match tag[0] {
+            TAG_TASK_START => RawEvent::TaskStart(read_u64(source)?, read_u64(source)?),
+            TAG_TASK_END => RawEvent::TaskEnd(read_u64(source)?),
+            TAG_CHILD => RawEvent::Child(read_u64(source)?),
+            TAG_TASK_METRIC => RawEvent::TaskMetric(read_u64(source)?),
+            TAG_BLOCK_START => RawEvent::BlockStart(read_u64(source)?),
+            TAG_BLOCK_END => RawEvent::BlockEnd(read_u64(source)?),
+            TAG_PARALLEL_DEPTH => RawEvent::ParallelDepth(read_u64(source)? as usize),
+            TAG_MERGED_TASKS => RawEvent::MergedTasks(read_u64(source)? as usize),
+            TAG_TASK_PRIORITY => RawEvent::TaskPriority(read_priority(read_u64(source)?)?),
+            TAG_MARKER => RawEvent::Marker(read_u64(source)? as SubGraphId, read_u64(source)?),
+            TAG_PHASE_START => {
+                RawEvent::PhaseStart(read_u64(source)? as SubGraphId, read_u64(source)?)
+            }
+            TAG_COUNTER => RawEvent::Counter(
+                read_u64(source)? as SubGraphId,
+                read_u64(source)?,
+                read_u64(source)?,
+            ),
+            TAG_SUBGRAPH_END_F64 => {
+                RawEvent::SubgraphEndF64(read_u64(source)? as SubGraphId, read_u64(source)?)
+            }
+            TAG_SUBGRAPH_START => RawEvent::SubgraphStart(read_u64(source)? as SubGraphId),
+            TAG_SUBGRAPH_END => {
+                RawEvent::SubgraphEnd(read_u64(source)? as SubGraphId, read_u64(source)? as usize)
+            }
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown event tag {}", tag),
+                ))
+            }
+        };
+        Ok(event)
+    }
+    /// Write one event.
+    pub(crate) fn write_to<W: Write>(&self, destination: &mut W) -> io::Result<()> {
+        match self {
+            RawEvent::TaskStart(id, time) => {
+                destination.write_all(&[TAG_TASK_START])?;
+                write_u64(*id, destination)?;
+                write_u64(*time, destination)?;
+            }
+            RawEvent::TaskEnd(time) => {
+                destination.write_all(&[TAG_TASK_END])?;
+                write_u64(*time, destination)?;
+            }
+            RawEvent::Child(id) => {
+                destination.write_all(&[TAG_CHILD])?;
+                write_u64(*id, destination)?;
+            }
+            RawEvent::TaskMetric(value) => {
+                destination.write_all(&[TAG_TASK_METRIC])?;
+                write_u64(*value, destination)?;
+            }
+            RawEvent::BlockStart(time) => {
+                destination.write_all(&[TAG_BLOCK_START])?;
+                write_u64(*time, destination)?;
+            }
+            RawEvent::BlockEnd(time) => {
+                destination.write_all(&[TAG_BLOCK_END])?;
+                write_u64(*time, destination)?;
+            }
+            RawEvent::ParallelDepth(depth) => {
+                destination.write_all(&[TAG_PARALLEL_DEPTH])?;
+                write_u64(*depth as u64, destination)?;
+            }
+            RawEvent::MergedTasks(count) => {
+                destination.write_all(&[TAG_MERGED_TASKS])?;
+                write_u64(*count as u64, destination)?;
+            }
+            RawEvent::TaskPriority(priority) => {
+                destination.write_all(&[TAG_TASK_PRIORITY])?;
+                write_u64(*priority as u64, destination)?;
+            }
+            RawEvent::Marker(label, time) => {
+                destination.write_all(&[TAG_MARKER])?;
+                write_u64(*label as u64, destination)?;
+                write_u64(*time, destination)?;
+            }
+            RawEvent::PhaseStart(label, time) => {
+                destination.write_all(&[TAG_PHASE_START])?;
+                write_u64(*label as u64, destination)?;
+                write_u64(*time, destination)?;
+            }
+            RawEvent::Counter(label, value, time) => {
+                destination.write_all(&[TAG_COUNTER])?;
+                write_u64(*label as u64, destination)?;
+                write_u64(*value, destination)?;
+                write_u64(*time, destination)?;
+            }
+            RawEvent::SubgraphEndF64(label, bits) => {
+                destination.write_all(&[TAG_SUBGRAPH_END_F64])?;
+                write_u64(*label as u64, destination)?;
+                write_u64(*bits, destination)?;
+            }
+            RawEvent::SubgraphStart(label) => {
+                destination.write_all(&[TAG_SUBGRAPH_START])?;
+                write_u64(*label as u64, destination)?;
+            }
+            RawEvent::SubgraphEnd(label, size) => {
+                destination.write_all(&[TAG_SUBGRAPH_END])?;
+                write_u64(*label as u64, destination)?;
+                write_u64(*size as u64, destination)?;
+            }
+        }
+        Ok(())
+    }
+    /// Read one event in the compact format, failing with `InvalidData` on unknown tags or
+    /// truncated input.
+    /// `previous_time` is the last time read on the same thread.
+    pub(crate) fn read_compact_from<R: Read>(
+        previous_time: &mut TimeStamp,
+        source: &mut R,
+    ) -> io::Result<RawEvent<SubGraphId>> {
+        RawEvent::decode_compact(previous_time, source).map_err(truncation)
+    }
+    fn decode_compact<R: Read>(
+        previous_time: &mut TimeStamp,
+        source: &mut R,
+    ) -> io::Result<RawEvent<SubGraphId>> {
+        let mut tag = [0u8];
+        source.read_exact(&mut tag)?;
+        let event = match tag[0] {
+            TAG_TASK_START => {
+                RawEvent::TaskStart(read_varint(source)?, read_time(previous_time, source)?)
+            }
+            TAG_TASK_END => RawEvent::TaskEnd(read_time(previous_time, source)?),
+            TAG_CHILD => RawEvent::Child(read_varint(source)?),
+            TAG_TASK_METRIC => RawEvent::TaskMetric(read_varint(source)?),
+            TAG_BLOCK_START => RawEvent::BlockStart(read_time(previous_time, source)?),
+            TAG_BLOCK_END => RawEvent::BlockEnd(read_time(previous_time, source)?),
+            TAG_PARALLEL_DEPTH => RawEvent::ParallelDepth(read_varint(source)? as usize),
+            TAG_MERGED_TASKS => RawEvent::MergedTasks(read_varint(source)? as usize),
+            TAG_TASK_PRIORITY => RawEvent::TaskPriority(read_priority(read_varint(source)?)?),
+            TAG_MARKER => RawEvent::Marker(
+                read_varint(source)? as SubGraphId,
+                read_time(previous_time, source)?,
+            ),
+            TAG_PHASE_START => RawEvent::PhaseStart(
+                read_varint(source)? as SubGraphId,
+                read_time(previous_time, source)?,
+            ),
+            TAG_COUNTER => RawEvent::Counter(
+                read_varint(source)? as SubGraphId,
+                read_varint(source)?,
+                read_time(previous_time, source)?,
+            ),
+            // float bits do not shrink as varints
+            TAG_SUBGRAPH_END_F64 => {
+                RawEvent::SubgraphEndF64(read_varint(source)? as SubGraphId, read_u64(source)?)
+            }
+            TAG_SUBGRAPH_START => RawEvent::SubgraphStart(read_varint(source)? as SubGraphId),
+            TAG_SUBGRAPH_END => RawEvent::SubgraphEnd(
+                read_varint(source)? as SubGraphId,
+                read_varint(source)? as usize,
+            ),
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown event tag {}", tag),
+                ))
+            }
+        };
+        Ok(event)
+    }
+    /// Write one event in the compact format.
+    /// `previous_time` is the last time written on the same thread.
+    pub(crate) fn write_compact_to<W: Write>(
+        &self,
+        previous_time: &mut TimeStamp,
+        destination: &mut W,
+    ) -> io::Result<()> {
+        match self {
+            RawEvent::TaskStart(id, time) => {
+                destination.write_all(&[TAG_TASK_START])?;
+                write_varint(*id, destination)?;
+                write_time(*time, previous_time, destination)?;
+            }
+            RawEvent::TaskEnd(time) => {
+                destination.write_all(&[TAG_TASK_END])?;
+                write_time(*time, previous_time, destination)?;
+            }
+            RawEvent::Child(id) => {
+                destination.write_all(&[TAG_CHILD])?;
+                write_varint(*id, destination)?;
+            }
+            RawEvent::TaskMetric(value) => {
+                destination.write_all(&[TAG_TASK_METRIC])?;
+                write_varint(*value, destination)?;
+            }
+            RawEvent::BlockStart(time) => {
+                destination.write_all(&[TAG_BLOCK_START])?;
+                write_time(*time, previous_time, destination)?;
+            }
+            RawEvent::BlockEnd(time) => {
+                destination.write_all(&[TAG_BLOCK_END])?;
+                write_time(*time, previous_time, destination)?;
+            }
+            RawEvent::ParallelDepth(depth) => {
+                destination.write_all(&[TAG_PARALLEL_DEPTH])?;
+                write_varint(*depth as u64, destination)?;
+            }
+            RawEvent::MergedTasks(count) => {
+                destination.write_all(&[TAG_MERGED_TASKS])?;
+                write_varint(*count as u64, destination)?;
+            }
+            RawEvent::TaskPriority(priority) => {
+                destination.write_all(&[TAG_TASK_PRIORITY])?;
+                write_varint(*priority as u64, destination)?;
+            }
+            RawEvent::Marker(label, time) => {
+                destination.write_all(&[TAG_MARKER])?;
+                write_varint(*label as u64, destination)?;
+                write_time(*time, previous_time, destination)?;
+            }
+            RawEvent::PhaseStart(label, time) => {
+                destination.write_all(&[TAG_PHASE_START])?;
+                write_varint(*label as u64, destination)?;
+                write_time(*time, previous_time, destination)?;
+            }
+            RawEvent::Counter(label, value, time) => {
+                destination.write_all(&[TAG_COUNTER])?;
+                write_varint(*label as u64, destination)?;
+                write_varint(*value, destination)?;
+                write_time(*time, previous_time, destination)?;
+            }
+            RawEvent::SubgraphEndF64(label, bits) => {
+                destination.write_all(&[TAG_SUBGRAPH_END_F64])?;
+                write_varint(*label as u64, destination)?;
+                write_u64(*bits, destination)?;
+            }
+            RawEvent::SubgraphStart(label) => {
+                destination.write_all(&[TAG_SUBGRAPH_START])?;
+                write_varint(*label as u64, destination)?;
+            }
+            RawEvent::SubgraphEnd(label, size) => {
+                destination.write_all(&[TAG_SUBGRAPH_END])?;
+                write_varint(*label as u64, destination)?;
+                write_varint(*size as u64, destination)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+use rayon_core::{subgraph, Logger};
+
+#[test]
+fn logging_switch() {
+    let logger = Logger::new();
+    subgraph("enabled", 1, || ());
+    Logger::set_enabled(false);
+    subgraph("disabled", 1, || ());
+    Logger::set_enabled(true);
+    subgraph("enabled again", 1, || ());
+    let logs = logger.extract_logs();
+    assert_eq!(
+        logs.labels,
+        vec!["enabled".to_string(), "enabled again".to_string()]
+    );
+}
@@ -394,6 +394,7 @@ where
     C: Consumer<P::Item>,
 {
     let splitter = LengthSplitter::new(producer.min_len(), producer.max_len(), len);
+    let _region = rayon_core::ParallelRegion::enter();
     return helper(len, false, splitter, producer, consumer);
 
     fn helper<P, C>(
@@ -449,6 +450,7 @@ where
     C: UnindexedConsumer<P::Item>,
 {
     let splitter = Splitter::new();
+    let _region = rayon_core::ParallelRegion::enter();
     bridge_unindexed_producer_consumer(false, splitter, producer, consumer)
 }
 
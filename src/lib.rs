@@ -93,6 +93,7 @@ mod split_producer;
 
 pub mod collections;
 pub mod iter;
+pub mod logging;
 pub mod option;
 pub mod prelude;
 pub mod range;
@@ -0,0 +1,27 @@
+//! Tasks logs: everything needed to record and analyze the execution of parallel code.
+//!
+//! `use rayon::logging::*` brings the whole logging API of `rayon-core` into scope,
+//! so there is no need to depend on `rayon-core` directly.
+//!
+//! ```
+//! use rayon::logging::*;
+//! use rayon::prelude::*;
+//!
+//! let logger = Logger::new();
+//! let pool = logger.pool_builder().num_threads(2).build().unwrap();
+//! let sum: u32 = pool.install(|| subgraph("sum", 100, || (0..100u32).into_par_iter().sum()));
+//! assert_eq!(sum, 4950);
+//! let logs = logger.extract_logs();
+//! assert_eq!(logs.label_timeline("sum").len(), 1);
+//! ```
+
+pub use rayon_core::wire_format;
+pub use rayon_core::{
+    custom_subgraph, custom_subgraph_f64, custom_subgraph_with_unit, log_external_task,
+    log_task_priority, sampled_subgraph, subgraph, tag_value,
+};
+pub use rayon_core::{
+    ActiveState, EventKinds, GrainReport, LogError, LogStatistics, Logger, ManualClock, PoolConfig,
+    Provenance, RawEvent, RawLogs, RawLogsBuilder, ResolvedEvent, SubGraphId, SubgraphMetric,
+    SubgraphStats, TaskId, TaskTree, ThreadStatistics, TimeStamp, EXTERNAL_TASK_FLAG,
+};